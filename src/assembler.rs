@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use crate::ram::Ram;
+use crate::symbols::Symbols;
+use crate::Result;
+
+/// an assembled program and where its labels landed
+#[derive(Debug)]
+pub struct Assembly {
+    pub program: Vec<u8>,
+    pub labels: Symbols,
+}
+
+/// Assembles Cowgod style mnemonic source into a program that loads at 0x200.
+///
+/// Reads everything the disassembler's listing writes, plus:
+/// - `name:` labels, on their own line or before an instruction
+/// - `NAME = 0x20` constants, usable anywhere a number is
+/// - `db 0x12, 34, 0b1010` data bytes
+/// - `sprite "#..##..#", "########"` sprite rows, `#` for a set pixel and
+///   `.` for a clear one, eight or sixteen pixels wide
+///
+/// Numbers are decimal, 0x hex or 0b binary. `;` starts a comment.
+pub fn assemble(source: &str) -> Result<Assembly> {
+    let mut symbols = HashMap::new();
+    let mut labels = Symbols::default();
+    let mut statements = vec![];
+    let mut addr = Ram::START_PRGM_REGISTER;
+
+    // first pass - parse every line and find where each label lands
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let at_line = |err: crate::Error| format!("line {}: {}", line_no, err);
+
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        while let Some((label, rest)) = split_label(line) {
+            define(&mut symbols, label, addr).map_err(at_line)?;
+            labels.insert(addr, label);
+            line = rest;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once('=') {
+            let value = parse_number(value).map_err(at_line)?;
+            define(&mut symbols, name.trim(), value).map_err(at_line)?;
+            continue;
+        }
+
+        let statement = Statement::parse(line).map_err(at_line)?;
+        addr += statement.len();
+        statements.push((line_no, statement));
+    }
+
+    // second pass - resolve labels and constants and encode
+    let mut program = vec![];
+    for (line_no, statement) in statements {
+        let bytes = statement
+            .encode(&symbols)
+            .map_err(|err| format!("line {}: {}", line_no, err))?;
+        program.extend(bytes);
+    }
+
+    if program.len() > Ram::MAX_PROGRAM_SIZE {
+        return Err(format!(
+            "{} bytes is larger than the {} byte maximum",
+            program.len(),
+            Ram::MAX_PROGRAM_SIZE
+        )
+        .into());
+    }
+
+    Ok(Assembly { program, labels })
+}
+
+enum Statement {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    /// db - numbers, labels or constants, a byte each
+    Data(Vec<String>),
+    Sprite(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    /// [I]
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    /// the 16 bit address of F000 NNNN
+    Long(String),
+    /// a number, label or constant
+    Value(String),
+}
+
+impl Statement {
+    fn parse(line: &str) -> Result<Self> {
+        let (mnemonic, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mnemonic = mnemonic.to_uppercase();
+        let args = args.trim();
+
+        let statement = match mnemonic.as_str() {
+            "DB" => Statement::Data(split_args(args).map(str::to_string).collect()),
+            "SPRITE" => {
+                let mut bytes = vec![];
+                for row in split_args(args) {
+                    bytes.extend(parse_sprite_row(row)?);
+                }
+                Statement::Sprite(bytes)
+            }
+            // SAVE Vx - Vy and LOAD Vx - Vy
+            "SAVE" | "LOAD" => {
+                let (x, y) = args
+                    .split_once('-')
+                    .ok_or_else(|| format!("expected {} Vx - Vy", mnemonic))?;
+                Statement::Instruction {
+                    mnemonic,
+                    operands: vec![Operand::parse(x), Operand::parse(y)],
+                }
+            }
+            _ => Statement::Instruction {
+                mnemonic,
+                operands: split_args(args).map(Operand::parse).collect(),
+            },
+        };
+
+        Ok(statement)
+    }
+
+    /// number of bytes the statement assembles to
+    fn len(&self) -> usize {
+        match self {
+            Statement::Instruction { operands, .. } => {
+                if let [Operand::I, Operand::Long(_)] = operands.as_slice() {
+                    4
+                } else {
+                    2
+                }
+            }
+            Statement::Data(values) => values.len(),
+            Statement::Sprite(bytes) => bytes.len(),
+        }
+    }
+
+    fn encode(&self, symbols: &HashMap<String, usize>) -> Result<Vec<u8>> {
+        let (mnemonic, operands) = match self {
+            Statement::Instruction { mnemonic, operands } => (mnemonic, operands),
+            Statement::Data(values) => {
+                return values
+                    .iter()
+                    .map(|value| resolve(value, symbols, 0xFF).map(|byte| byte as u8))
+                    .collect();
+            }
+            Statement::Sprite(bytes) => return Ok(bytes.clone()),
+        };
+
+        let value = |value: &str, max: usize| resolve(value, symbols, max).map(|v| v as u16);
+        let xy = |x: u8, y: u8| (x as u16) << 8 | (y as u16) << 4;
+
+        use Operand::*;
+        let opcode = match (mnemonic.as_str(), operands.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCD", [Value(n)]) => 0x00C0 | value(n, 0xF)?,
+            ("SCU", [Value(n)]) => 0x00D0 | value(n, 0xF)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("SYS", [Value(addr)]) => value(addr, 0xFFF)?,
+            ("JP", [Value(addr)]) => 0x1000 | value(addr, 0xFFF)?,
+            ("JP", [V(0), Value(addr)]) => 0xB000 | value(addr, 0xFFF)?,
+            ("CALL", [Value(addr)]) => 0x2000 | value(addr, 0xFFF)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+            ("SE", [V(x), Value(nn)]) => 0x3000 | xy(*x, 0) | value(nn, 0xFF)?,
+            ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+            ("SNE", [V(x), Value(nn)]) => 0x4000 | xy(*x, 0) | value(nn, 0xFF)?,
+            ("SAVE", [V(x), V(y)]) => 0x5002 | xy(*x, *y),
+            ("LOAD", [V(x), V(y)]) => 0x5003 | xy(*x, *y),
+            ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+            ("LD", [V(x), DT]) => 0xF007 | xy(*x, 0),
+            ("LD", [V(x), K]) => 0xF00A | xy(*x, 0),
+            ("LD", [DT, V(x)]) => 0xF015 | xy(*x, 0),
+            ("LD", [ST, V(x)]) => 0xF018 | xy(*x, 0),
+            ("LD", [F, V(x)]) => 0xF029 | xy(*x, 0),
+            ("LD", [HF, V(x)]) => 0xF030 | xy(*x, 0),
+            ("LD", [B, V(x)]) => 0xF033 | xy(*x, 0),
+            ("LD", [IndirectI, V(x)]) => 0xF055 | xy(*x, 0),
+            ("LD", [V(x), IndirectI]) => 0xF065 | xy(*x, 0),
+            ("LD", [R, V(x)]) => 0xF075 | xy(*x, 0),
+            ("LD", [V(x), R]) => 0xF085 | xy(*x, 0),
+            ("LD", [I, Long(addr)]) => {
+                let [hi_byte, lo_byte] = value(addr, 0xFFFF)?.to_be_bytes();
+                return Ok(vec![0xF0, 0x00, hi_byte, lo_byte]);
+            }
+            ("LD", [I, Value(addr)]) => 0xA000 | value(addr, 0xFFF)?,
+            ("LD", [V(x), Value(nn)]) => 0x6000 | xy(*x, 0) | value(nn, 0xFF)?,
+            ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+            ("ADD", [I, V(x)]) => 0xF01E | xy(*x, 0),
+            ("ADD", [V(x), Value(nn)]) => 0x7000 | xy(*x, 0) | value(nn, 0xFF)?,
+            ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+            ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+            ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+            ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+            ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+            ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+            ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+            ("RND", [V(x), Value(nn)]) => 0xC000 | xy(*x, 0) | value(nn, 0xFF)?,
+            ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(*x, *y) | value(n, 0xF)?,
+            ("SKP", [V(x)]) => 0xE09E | xy(*x, 0),
+            ("SKNP", [V(x)]) => 0xE0A1 | xy(*x, 0),
+            ("PLANE", [Value(n)]) => 0xF001 | value(n, 0xF)? << 8,
+            ("AUDIO", []) => 0xF002,
+            ("PITCH", [V(x)]) => 0xF03A | xy(*x, 0),
+            _ => {
+                return Err(
+                    format!("no {} instruction takes operands {:?}", mnemonic, operands).into(),
+                )
+            }
+        };
+
+        Ok(u16::to_be_bytes(opcode).to_vec())
+    }
+}
+
+impl Operand {
+    fn parse(operand: &str) -> Self {
+        let operand = operand.trim();
+
+        if let Some(addr) = operand
+            .strip_prefix("long ")
+            .or_else(|| operand.strip_prefix("LONG "))
+        {
+            return Operand::Long(addr.trim().to_string());
+        }
+
+        match operand.to_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::DT,
+            "ST" => Operand::ST,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::HF,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            upper => upper
+                .strip_prefix('V')
+                .filter(|x| x.len() == 1)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .map_or_else(|| Operand::Value(operand.to_string()), Operand::V),
+        }
+    }
+}
+
+fn split_args(args: &str) -> impl Iterator<Item = &str> {
+    args.split(',').map(str::trim).filter(|arg| !arg.is_empty())
+}
+
+/// splits "name: rest" into its label and whatever follows it
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    is_identifier(label).then(|| (label, rest.trim()))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn define(symbols: &mut HashMap<String, usize>, name: &str, value: usize) -> Result<()> {
+    if !is_identifier(name) {
+        return Err(format!("'{}' isn't a valid name", name).into());
+    }
+
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(format!("'{}' is already defined", name).into());
+    }
+
+    Ok(())
+}
+
+/// a number, label or constant, checked against max
+fn resolve(value: &str, symbols: &HashMap<String, usize>, max: usize) -> Result<usize> {
+    let resolved = match symbols.get(value) {
+        Some(&resolved) => resolved,
+        None if is_identifier(value) => return Err(format!("undefined name '{}'", value).into()),
+        None => parse_number(value)?,
+    };
+
+    if resolved > max {
+        return Err(format!("{} is larger than the maximum of {:#X}", value, max).into());
+    }
+
+    Ok(resolved)
+}
+
+/// parses decimal, 0x hex or 0b binary
+fn parse_number(number: &str) -> Result<usize> {
+    let number = number.trim();
+    let lower = number.to_lowercase();
+
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        usize::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+
+    parsed.map_err(|_| format!("expected a number, got '{}'", number).into())
+}
+
+/// "#..##..#" -> 0b10011001. Rows are a multiple of 8 pixels wide.
+fn parse_sprite_row(row: &str) -> Result<Vec<u8>> {
+    let pixels = row
+        .strip_prefix('"')
+        .and_then(|row| row.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a quoted sprite row, got {}", row))?;
+
+    if pixels.is_empty() || pixels.len() % 8 != 0 {
+        return Err(format!("sprite row \"{}\" isn't a multiple of 8 pixels", pixels).into());
+    }
+
+    pixels
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            chunk.iter().try_fold(0u8, |byte, pixel| match pixel {
+                b'#' => Ok(byte << 1 | 1),
+                b'.' => Ok(byte << 1),
+                _ => Err(format!("sprite pixels are '#' or '.', got '{}'", *pixel as char).into()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler;
+    use crate::symbols::Symbols;
+
+    #[test]
+    fn assembles_instructions() {
+        let source = "
+            CLS
+            LD V1, 0x20 ; a comment
+            DRW V0, V1, 5
+            SAVE V2 - V4
+            LD I, long 0x1234
+            ld [i], v3
+        ";
+        assert_eq!(
+            assemble(source).unwrap().program,
+            [0x00, 0xE0, 0x61, 0x20, 0xD0, 0x15, 0x52, 0x42, 0xF0, 0x00, 0x12, 0x34, 0xF3, 0x55]
+        );
+    }
+
+    #[test]
+    fn resolves_labels_and_constants() {
+        let source = "
+            SPEED = 3
+            start: CALL draw
+            JP start
+            draw:
+                ADD V0, SPEED
+                RET
+        ";
+        assert_eq!(
+            assemble(source).unwrap().program,
+            [0x22, 0x04, 0x12, 0x00, 0x70, 0x03, 0x00, 0xEE]
+        );
+        assert_eq!(
+            assemble(source).unwrap().labels.to_string(),
+            "start = 0x200\ndraw = 0x204\n"
+        );
+    }
+
+    #[test]
+    fn assembles_data_and_sprites() {
+        let source = "
+            db 0x12, 34, 0b1010
+            sprite \"#..##..#\", \"################\"
+        ";
+        assert_eq!(
+            assemble(source).unwrap().program,
+            [0x12, 34, 0b1010, 0b10011001, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        let err = assemble("CLS\nLD VG, 1").unwrap_err().to_string();
+        assert!(err.starts_with("line 2:"), "{}", err);
+        assert!(assemble("JP nowhere").is_err());
+        assert!(assemble("LD V0, 0x100").is_err());
+        assert!(assemble("a: CLS\na: CLS").is_err());
+        assert!(assemble("sprite \"#..#\"").is_err());
+    }
+
+    #[test]
+    fn round_trips_disassembly() {
+        for rom in [
+            &include_bytes!("../games/Ibm.ch8")[..],
+            &include_bytes!("../games/test_opcode.ch8")[..],
+            &[0xF0, 0x00, 0x12, 0x34, 0x51, 0x21, 0xAB],
+        ] {
+            let listing = disassembler::listing(rom, &Symbols::default());
+            assert_eq!(assemble(&listing).unwrap().program, rom);
+        }
+    }
+}
@@ -0,0 +1,909 @@
+use serde::{Deserialize, Serialize};
+
+use crate::display::{Display, DrawInfo};
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::ram::{Ram, Timer};
+use crate::rng::Rng;
+use crate::scheduler::Scheduler;
+
+/// The CHIP-8 machine itself - memory, framebuffer, keypad and timers.
+///
+/// Chip8 has no window, audio device or event loop of its own. A frontend
+/// drives it by calling `step`/`run_frame`, feeding key state in through
+/// `set_key` and reading the framebuffer back out of `display`.
+///
+/// Time is emulated - the scheduler decides how many instructions make up
+/// a 60 Hz frame and the timers tick once per frame, however fast or slow
+/// the frontend calls in.
+#[derive(Serialize, Deserialize)]
+pub struct Chip8 {
+    pub ram: Ram,
+    pub display: Display,
+    pub quirks: Quirks,
+    pub scheduler: Scheduler,
+    keypad: [bool; 16],
+    /// SUPER-CHIP RPL user flags saved and restored by FX75/FX85
+    rpl_flags: [u8; 16],
+    rng: Rng,
+    /// set while FX0A is blocking on a key
+    key_wait: Option<KeyWait>,
+    /// instructions executed since boot
+    executed: u64,
+    #[serde(skip)]
+    draw_flag: bool,
+    halted: bool,
+}
+
+/// FX0A state. Like the COSMAC VIP, the wait ends when a key that was
+/// pressed during the wait is released, not on the press itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct KeyWait {
+    register: u8,
+    pressed: Option<u8>,
+}
+
+impl Chip8 {
+    pub fn new(program: &[u8], quirks: Quirks) -> Result<Self, Chip8Error> {
+        Ok(Chip8 {
+            ram: Ram::load(program, quirks.memory_size())?,
+            display: Display::default(),
+            quirks,
+            scheduler: Scheduler::default(),
+            keypad: [false; 16],
+            rpl_flags: [0; 16],
+            rng: Rng::from_entropy(),
+            key_wait: None,
+            executed: 0,
+            draw_flag: false,
+            halted: false,
+        })
+    }
+
+    /// sets the state of a hex key (0x0 - 0xF)
+    pub fn set_key(&mut self, hex: u8, down: bool) {
+        let Some(key) = self.keypad.get_mut(hex as usize) else {
+            return;
+        };
+        *key = down;
+
+        if let Some(wait) = self.key_wait.as_mut() {
+            if down {
+                wait.pressed.get_or_insert(hex);
+            } else if wait.pressed == Some(hex) {
+                self.ram.V[wait.register as usize] = hex;
+                self.key_wait = None;
+            }
+        }
+    }
+
+    /// takes the held keys from live, for a machine restored from a
+    /// snapshot - keys held back then aren't necessarily held now, and
+    /// would otherwise stay down until pressed and released again
+    pub fn keep_keys_from(&mut self, live: &Chip8) {
+        self.keypad = live.keypad;
+
+        // a key wait can only end on the release of a key that's still down
+        if let Some(wait) = self.key_wait.as_mut() {
+            if wait.pressed.is_some_and(|hex| !self.keypad[hex as usize]) {
+                wait.pressed = None;
+            }
+        }
+    }
+
+    /// returns true if the hex key is currently held
+    pub fn is_pressed(&self, hex: u8) -> bool {
+        self.keypad.get(hex as usize).copied().unwrap_or(false)
+    }
+
+    /// returns true while the sound timer is running
+    pub fn is_beeping(&self) -> bool {
+        self.ram.sound_timer > 0
+    }
+
+    /// returns true once the program has exited with 00FD or been halted
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// instructions executed since boot - cycles spent waiting on FX0A don't count
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// returns true while FX0A is waiting on a key
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    /// while FX0A is waiting, the register it fills and the key pressed so far
+    pub fn key_wait(&self) -> Option<(u8, Option<u8>)> {
+        self.key_wait.map(|wait| (wait.register, wait.pressed))
+    }
+
+    /// stops the machine - step does nothing from here on
+    pub fn halt(&mut self) {
+        self.halted = true;
+    }
+
+    /// returns true if the framebuffer changed since the last call
+    pub fn take_draw_flag(&mut self) -> bool {
+        std::mem::take(&mut self.draw_flag)
+    }
+
+    /// makes the next take_draw_flag return true
+    pub fn force_redraw(&mut self) {
+        self.draw_flag = true;
+    }
+
+    /// returns the instruction PC currently points at without executing it
+    pub fn peek_instruction(&self) -> Instruction {
+        let byte = |addr: usize| self.ram.mem.get(addr).copied().unwrap_or(0);
+        Instruction::from([byte(self.ram.PC), byte(self.ram.PC + 1)])
+    }
+
+    /// executes instructions up to the end of the current frame, stopping
+    /// early at the first error
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        let frame = self.scheduler.frame();
+
+        while self.scheduler.frame() == frame && !self.halted {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// decrements the delay and sound timers - called once per 60 Hz frame
+    fn tick_timers(&mut self) {
+        self.ram.delay_timer = self.ram.delay_timer.saturating_sub(1);
+        self.ram.sound_timer = self.ram.sound_timer.saturating_sub(1);
+    }
+
+    /// skips the next instruction - XO-CHIP's F000 NNNN is four bytes long
+    pub fn skip_instruction(&mut self) {
+        let pc = self.ram.PC;
+        let is_long_load = self.ram.mem.get(pc..pc + 2) == Some(&[0xF0, 0x00]);
+        self.ram.PC += if is_long_load { 4 } else { 2 };
+    }
+
+    /// moves I past FX55/FX65's registers, or only by X on CHIP-48
+    fn increment_index_after_load_store(&mut self, x: u8) {
+        if self.quirks.load_store_increments_i {
+            let by = if self.quirks.load_store_increments_by_x {
+                x as u16
+            } else {
+                x as u16 + 1
+            };
+            self.ram.I = self.ram.I.wrapping_add(by);
+        }
+    }
+
+    /// errors unless len bytes starting at addr are all in memory
+    fn check_memory(&self, pc: usize, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if addr + len <= self.ram.mem.len() {
+            Ok(())
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds { pc, addr, len })
+        }
+    }
+
+    /// registers VX through VY, in reverse when X > Y
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    fn get_nums(value: &u8) -> (u8, u8, u8) {
+        let hundreds_digit = value / 100;
+        let tens_digit = (value / 10) % 10;
+        let ones_digit = value % 10;
+
+        (hundreds_digit, tens_digit, ones_digit)
+    }
+
+    /// executes one instruction, ticking the timers if it completes a frame.
+    /// While FX0A is waiting on a key the cycle passes without executing anything.
+    ///
+    /// On error nothing is executed or ticked and PC is left on the
+    /// offending instruction.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
+        }
+
+        if self.key_wait.is_none() {
+            let pc = self.ram.PC;
+
+            if let Err(err) = self.execute(pc) {
+                self.ram.PC = pc;
+                return Err(err);
+            }
+            self.executed += 1;
+        }
+
+        if self.scheduler.tick() {
+            self.tick_timers();
+        }
+
+        Ok(())
+    }
+
+    /// fetches, decodes and executes the instruction at pc. Everything is
+    /// checked before anything is written, so a failed instruction has no effect.
+    fn execute(&mut self, pc: usize) -> Result<(), Chip8Error> {
+        let instruction_bytes = self.ram.get_next_instruction()?;
+        let unknown = Chip8Error::UnknownOpcode {
+            pc,
+            opcode: u16::from_be_bytes(instruction_bytes),
+        };
+
+        let Instruction {
+            first_nibble,
+            x,
+            y,
+            n,
+            nn,
+            nnn,
+            ..
+        } = Instruction::from(instruction_bytes);
+
+        match (first_nibble, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => {
+                self.display.clear();
+                self.draw_flag = true;
+            }
+            (0x0, 0x0, 0xE, 0xE) => {
+                self.ram.PC = self
+                    .ram
+                    .remove_addr()
+                    .ok_or(Chip8Error::StackUnderflow { pc })?;
+            }
+            (0x0, 0x0, 0xD, _) => {
+                self.display.scroll_up(n as usize);
+                self.draw_flag = true;
+            }
+            (0x0, 0x0, 0xC, _) => {
+                self.display.scroll_down(n as usize);
+                self.draw_flag = true;
+            }
+            (0x0, 0x0, 0xF, 0xB) => {
+                self.display.scroll_right(4);
+                self.draw_flag = true;
+            }
+            (0x0, 0x0, 0xF, 0xC) => {
+                self.display.scroll_left(4);
+                self.draw_flag = true;
+            }
+            (0x0, 0x0, 0xF, 0xD) => {
+                self.halted = true;
+            }
+            (0x0, 0x0, 0xF, 0xE) | (0x0, 0x0, 0xF, 0xF) => {
+                self.display.set_hires(n == 0xF);
+                self.draw_flag = true;
+            }
+            (0x1, _, _, _) => {
+                self.ram.PC = nnn as usize;
+            }
+            (0x2, _, _, _) => {
+                if !self.ram.store_addr(self.ram.PC) {
+                    return Err(Chip8Error::StackOverflow { pc });
+                }
+                self.ram.PC = nnn as usize;
+            }
+            (0x3, _, _, _) => {
+                if self.ram.V[x as usize] == nn {
+                    self.skip_instruction();
+                }
+            }
+            (0x4, _, _, _) => {
+                if self.ram.V[x as usize] != nn {
+                    self.skip_instruction();
+                }
+            }
+            (0x5, _, _, 0) => {
+                if self.ram.V[x as usize] == self.ram.V[y as usize] {
+                    self.skip_instruction();
+                }
+            }
+            (0x5, _, _, 2) => {
+                let current_index = self.ram.I as usize;
+                self.check_memory(pc, current_index, x.abs_diff(y) as usize + 1)?;
+                Self::register_range(x, y)
+                    .enumerate()
+                    .for_each(|(i, register)| {
+                        self.ram.mem[current_index + i] = self.ram.V[register];
+                    });
+            }
+            (0x5, _, _, 3) => {
+                let current_index = self.ram.I as usize;
+                self.check_memory(pc, current_index, x.abs_diff(y) as usize + 1)?;
+                Self::register_range(x, y)
+                    .enumerate()
+                    .for_each(|(i, register)| {
+                        self.ram.V[register] = self.ram.mem[current_index + i];
+                    });
+            }
+            (0x6, _, _, _) => self.ram.set_register(x as usize, nn),
+            (0x7, _, _, _) => {
+                let (updated, _) = self.ram.V[x as usize].overflowing_add(nn);
+                self.ram.V[x as usize] = updated;
+            }
+            (0x8, _, _, _) => match n {
+                0 => self.ram.V[x as usize] = self.ram.V[y as usize],
+                1..=3 => {
+                    let vy = self.ram.V[y as usize];
+                    match n {
+                        1 => self.ram.V[x as usize] |= vy,
+                        2 => self.ram.V[x as usize] &= vy,
+                        _ => self.ram.V[x as usize] ^= vy,
+                    }
+
+                    if self.quirks.logic_resets_vf {
+                        self.ram.update_vf_register(false);
+                    }
+                }
+                4 => {
+                    let (updated, did_overflow) =
+                        self.ram.V[x as usize].overflowing_add(self.ram.V[y as usize]);
+                    self.ram.V[x as usize] = updated;
+                    self.ram.update_vf_register(did_overflow);
+                }
+                5 => {
+                    self.ram
+                        .update_vf_register(self.ram.V[x as usize] > self.ram.V[y as usize]);
+                    let (updated, _) =
+                        self.ram.V[x as usize].overflowing_sub(self.ram.V[y as usize]);
+                    self.ram.V[x as usize] = updated;
+                }
+                6 => {
+                    if self.quirks.shift_uses_vy {
+                        self.ram.V[x as usize] = self.ram.V[y as usize];
+                    }
+                    // value that will be shifted out - first big end bit
+                    let is_one = self.ram.V[x as usize] & 1 == 1;
+                    self.ram.update_vf_register(is_one);
+                    self.ram.V[x as usize] >>= 1;
+                }
+                7 => {
+                    self.ram
+                        .update_vf_register(self.ram.V[y as usize] > self.ram.V[x as usize]);
+                    let (updated, _) =
+                        self.ram.V[y as usize].overflowing_sub(self.ram.V[x as usize]);
+                    self.ram.V[x as usize] = updated;
+                }
+                14 => {
+                    if self.quirks.shift_uses_vy {
+                        self.ram.V[x as usize] = self.ram.V[y as usize];
+                    }
+                    let is_one = self.ram.V[x as usize] >> 7 == 1;
+                    // value that will be shifted out - final big end bit
+                    self.ram.update_vf_register(is_one);
+                    self.ram.V[x as usize] <<= 1;
+                }
+                _ => return Err(unknown),
+            },
+            (0x9, _, _, _) => {
+                if self.ram.V[x as usize] != self.ram.V[y as usize] {
+                    self.skip_instruction();
+                }
+            }
+            (0xA, _, _, _) => {
+                self.ram.set_i_register(nnn);
+            }
+            (0xB, _, _, _) => {
+                let offset_register = if self.quirks.jump_uses_vx { x } else { 0 };
+                self.ram.PC = (self.ram.V[offset_register as usize] as u16 + nnn) as usize;
+            }
+            (0xC, _, _, _) => {
+                self.ram.V[x as usize] = self.rng.next_u8() & nn;
+            }
+            (0xD, _, _, _) => {
+                // DXY0 draws a SUPER-CHIP 16x16 sprite
+                let (row_count, sprite_width) = if n == 0 { (16, 16) } else { (n, 8) };
+                let sprite_start_idx = self.ram.I as usize;
+                let plane_count = self.display.planes.count_ones() as usize;
+                let sprite_len = row_count as usize * sprite_width as usize / 8 * plane_count;
+                self.check_memory(pc, sprite_start_idx, sprite_len)?;
+                let sprite_end_idx = sprite_start_idx + sprite_len;
+
+                let sprites = &self.ram.mem[sprite_start_idx..sprite_end_idx].to_vec();
+                let coords = (self.ram.V[x as usize], self.ram.V[y as usize]);
+
+                let flipped_bit_callback = |did_flip: bool| {
+                    self.ram.update_vf_register(did_flip);
+                };
+
+                let draw_info = DrawInfo {
+                    coords,
+                    sprites: sprites.as_slice(),
+                    row_count,
+                    sprite_width,
+                    wrap: self.quirks.wrap_sprites,
+                };
+
+                self.display.draw(draw_info, flipped_bit_callback);
+                self.draw_flag = true;
+            }
+            (0xE, _, _, _) => match n {
+                0xE => {
+                    if self.is_pressed(self.ram.V[x as usize]) {
+                        self.skip_instruction();
+                    }
+                }
+                0x1 => {
+                    if !self.is_pressed(self.ram.V[x as usize]) {
+                        self.skip_instruction();
+                    }
+                }
+                _ => return Err(unknown),
+            },
+            (0xF, _, _, _) => match n {
+                0 if x == 0 && y == 0 => {
+                    // F000 NNNN - the address is the next two bytes
+                    let [hi_byte, lo_byte] = self.ram.get_next_instruction()?;
+                    self.ram
+                        .set_i_register(u16::from_be_bytes([hi_byte, lo_byte]));
+                }
+                1 if y == 0 => {
+                    self.display.planes = x & 0b11;
+                }
+                0 if y == 3 => {
+                    let char = self.ram.V[x as usize] & 0xF;
+                    self.ram.I = (Ram::BIG_FONT_START + char as usize * 10) as u16;
+                }
+                3 => {
+                    let (hundred, ten, one) = Self::get_nums(&self.ram.V[x as usize]);
+                    let current_idx = self.ram.I;
+                    self.check_memory(pc, current_idx as usize, 3)?;
+                    self.ram.mem[current_idx as usize] = hundred;
+                    self.ram.mem[(current_idx + 1) as usize] = ten;
+                    self.ram.mem[(current_idx + 2) as usize] = one;
+                }
+                5 => match y {
+                    1 => self
+                        .ram
+                        .set_timer_register(Timer::Delay, self.ram.V[x as usize]),
+                    5 => {
+                        let current_index = self.ram.I as usize;
+                        self.check_memory(pc, current_index, x as usize + 1)?;
+                        self.ram.V[0..=x as usize]
+                            .iter()
+                            .enumerate()
+                            .for_each(|(i, val)| {
+                                self.ram.mem[current_index + i] = *val;
+                            });
+
+                        self.increment_index_after_load_store(x);
+                    }
+                    6 => {
+                        let current_index = self.ram.I as usize;
+                        self.check_memory(pc, current_index, x as usize + 1)?;
+                        (current_index..=current_index + x as usize)
+                            .enumerate()
+                            .for_each(|(i, addr)| {
+                                self.ram.V[i] = self.ram.mem[addr];
+                            });
+
+                        self.increment_index_after_load_store(x);
+                    }
+                    7 => {
+                        self.rpl_flags[..=x as usize].copy_from_slice(&self.ram.V[..=x as usize]);
+                    }
+                    8 => {
+                        self.ram.V[..=x as usize].copy_from_slice(&self.rpl_flags[..=x as usize]);
+                    }
+                    _ => return Err(unknown),
+                },
+                7 => {
+                    let current_delay_value = self.ram.get_timer_register(Timer::Delay);
+                    self.ram.set_register(x as usize, current_delay_value);
+                }
+                8 => self
+                    .ram
+                    .set_timer_register(Timer::Sound, self.ram.V[x as usize]),
+                9 => {
+                    // like the VIP, only the low nibble picks the digit
+                    let char = self.ram.V[x as usize] & 0xF;
+                    self.ram.I = (Ram::FONT_START + char as usize * 5) as u16;
+                }
+                0xA => {
+                    self.key_wait = Some(KeyWait {
+                        register: x,
+                        pressed: None,
+                    });
+                }
+                0xE => {
+                    let (update, did_overflow) =
+                        self.ram.I.overflowing_add(self.ram.V[x as usize] as u16);
+                    self.ram.set_i_register(update);
+                    self.ram.update_vf_register(did_overflow);
+                }
+                _ => return Err(unknown),
+            },
+            _ => return Err(unknown),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IBM: &[u8] = include_bytes!("../games/Ibm.ch8");
+
+    #[test]
+    fn sets_and_releases_keys() {
+        let mut chip8 = Chip8::new(&[], Quirks::default()).unwrap();
+        chip8.set_key(0xA, true);
+        assert!(chip8.is_pressed(0xA));
+        chip8.set_key(0xA, false);
+        assert!(!chip8.is_pressed(0xA));
+    }
+
+    #[test]
+    fn holds_multiple_keys() {
+        // E19E 6001 E29E 6101 - SKP V1; LD V0, 1; SKP V2; LD V1, 1
+        let program = [0xE1, 0x9E, 0x60, 0x01, 0xE2, 0x9E, 0x61, 0x01];
+        let mut chip8 = Chip8::new(&program, Quirks::default()).unwrap();
+        chip8.ram.V[1] = 0x5;
+        chip8.ram.V[2] = 0x8;
+        chip8.set_key(0x5, true);
+        chip8.set_key(0x8, true);
+
+        (0..2).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.V[0], 0);
+        assert_eq!(chip8.ram.PC, Ram::START_PRGM_REGISTER + 8);
+    }
+
+    #[test]
+    fn steps_single_instruction() {
+        // 6A2F - LD VA, 0x2F
+        let mut chip8 = Chip8::new(&[0x6A, 0x2F], Quirks::default()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.V[0xA], 0x2F);
+        assert_eq!(chip8.ram.PC, Ram::START_PRGM_REGISTER + 2);
+    }
+
+    #[test]
+    fn waits_for_key_release() {
+        // F30A 6001 - LD V3, K; LD V0, 1
+        let mut chip8 = Chip8::new(&[0xF3, 0x0A, 0x60, 0x01], Quirks::default()).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.V[0], 0);
+
+        chip8.set_key(0x7, true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.V[0], 0);
+
+        chip8.set_key(0x7, false);
+        assert_eq!(chip8.ram.V[3], 0x7);
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.V[0], 1);
+    }
+
+    #[test]
+    fn ignores_keys_held_before_waiting() {
+        // F30A - LD V3, K
+        let mut chip8 = Chip8::new(&[0xF3, 0x0A], Quirks::default()).unwrap();
+        chip8.set_key(0x2, true);
+        chip8.step().unwrap();
+
+        chip8.set_key(0x2, false);
+        assert!(chip8.key_wait.is_some());
+
+        chip8.set_key(0x9, true);
+        chip8.set_key(0x9, false);
+        assert!(chip8.key_wait.is_none());
+        assert_eq!(chip8.ram.V[3], 0x9);
+    }
+
+    #[test]
+    fn keeps_live_keys_over_restored_ones() {
+        // F30A - LD V3, K
+        let mut snapshot = Chip8::new(&[0xF3, 0x0A], Quirks::default()).unwrap();
+        snapshot.step().unwrap();
+        snapshot.set_key(0x5, true);
+        snapshot.set_key(0x7, true);
+
+        let mut live = Chip8::new(&[0xF3, 0x0A], Quirks::default()).unwrap();
+        live.set_key(0x2, true);
+
+        snapshot.keep_keys_from(&live);
+        assert!(!snapshot.is_pressed(0x5) && !snapshot.is_pressed(0x7));
+        assert!(snapshot.is_pressed(0x2));
+
+        // the wait no longer hangs on 5's release
+        snapshot.set_key(0x9, true);
+        snapshot.set_key(0x9, false);
+        assert!(!snapshot.is_waiting_for_key());
+        assert_eq!(snapshot.ram.V[3], 0x9);
+    }
+
+    #[test]
+    fn ticks_timers_while_waiting_for_key() {
+        // 6005 F015 F30A - LD V0, 5; LD DT, V0; LD V3, K
+        let program = [0x60, 0x05, 0xF0, 0x15, 0xF3, 0x0A];
+        let mut chip8 = Chip8::new(&program, Quirks::default()).unwrap();
+        (0..5).for_each(|_| chip8.run_frame().unwrap());
+        assert_eq!(chip8.ram.delay_timer, 0);
+        assert_eq!(chip8.ram.PC, Ram::START_PRGM_REGISTER + 6);
+    }
+
+    #[test]
+    fn shifts_with_quirks() {
+        // 6105 6203 8126 - LD V1, 5; LD V2, 3; SHR V1, V2
+        let program = [0x61, 0x05, 0x62, 0x03, 0x81, 0x26];
+
+        let mut vip = Chip8::new(&program, Quirks::cosmac_vip()).unwrap();
+        (0..3).for_each(|_| vip.step().unwrap());
+        assert_eq!(vip.ram.V[1], 1);
+
+        let mut schip = Chip8::new(&program, Quirks::schip()).unwrap();
+        (0..3).for_each(|_| schip.step().unwrap());
+        assert_eq!(schip.ram.V[1], 2);
+    }
+
+    #[test]
+    fn jumps_with_quirks() {
+        // 6004 6102 B120 - LD V0, 4; LD V1, 2; JP V0, 0x120
+        let program = [0x60, 0x04, 0x61, 0x02, 0xB1, 0x20];
+
+        let mut vip = Chip8::new(&program, Quirks::cosmac_vip()).unwrap();
+        (0..3).for_each(|_| vip.step().unwrap());
+        assert_eq!(vip.ram.PC, 0x124);
+
+        let mut schip = Chip8::new(&program, Quirks::schip()).unwrap();
+        (0..3).for_each(|_| schip.step().unwrap());
+        assert_eq!(schip.ram.PC, 0x122);
+    }
+
+    #[test]
+    fn stores_registers_with_quirks() {
+        // A300 F255 - LD I, 0x300; LD [I], V2
+        let program = [0xA3, 0x00, 0xF2, 0x55];
+
+        let mut vip = Chip8::new(&program, Quirks::cosmac_vip()).unwrap();
+        (0..2).for_each(|_| vip.step().unwrap());
+        assert_eq!(vip.ram.I, 0x303);
+
+        let mut chip48 = Chip8::new(&program, Quirks::chip48()).unwrap();
+        (0..2).for_each(|_| chip48.step().unwrap());
+        assert_eq!(chip48.ram.I, 0x302);
+
+        let mut schip = Chip8::new(&program, Quirks::schip()).unwrap();
+        (0..2).for_each(|_| schip.step().unwrap());
+        assert_eq!(schip.ram.I, 0x300);
+    }
+
+    #[test]
+    fn resets_vf_with_quirks() {
+        // 6F01 8011 - LD VF, 1; OR V0, V1
+        let program = [0x6F, 0x01, 0x80, 0x11];
+
+        let mut vip = Chip8::new(&program, Quirks::cosmac_vip()).unwrap();
+        (0..2).for_each(|_| vip.step().unwrap());
+        assert_eq!(vip.ram.V[0xF], 0);
+
+        let mut schip = Chip8::new(&program, Quirks::schip()).unwrap();
+        (0..2).for_each(|_| schip.step().unwrap());
+        assert_eq!(schip.ram.V[0xF], 1);
+    }
+
+    #[test]
+    fn switches_resolution() {
+        // 00FF 00FE - HIGH; LOW
+        let mut chip8 = Chip8::new(&[0x00, 0xFF, 0x00, 0xFE], Quirks::schip()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.width(), 128);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.width(), 64);
+    }
+
+    #[test]
+    fn points_at_big_font() {
+        // 6007 F030 - LD V0, 7; LD HF, V0
+        let mut chip8 = Chip8::new(&[0x60, 0x07, 0xF0, 0x30], Quirks::schip()).unwrap();
+        (0..2).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.I as usize, Ram::BIG_FONT_START + 70);
+    }
+
+    #[test]
+    fn saves_and_loads_rpl_flags() {
+        // 6011 6122 F175 6000 6100 F185 - LD V0..V1; LD R, V1; clear; LD V1, R
+        let program = [
+            0x60, 0x11, 0x61, 0x22, 0xF1, 0x75, 0x60, 0x00, 0x61, 0x00, 0xF1, 0x85,
+        ];
+        let mut chip8 = Chip8::new(&program, Quirks::schip()).unwrap();
+        (0..6).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.V[0..2], [0x11, 0x22]);
+    }
+
+    #[test]
+    fn exits() {
+        // 00FD - EXIT
+        let mut chip8 = Chip8::new(&[0x00, 0xFD, 0x60, 0x01], Quirks::schip()).unwrap();
+        (0..2).for_each(|_| chip8.step().unwrap());
+        assert!(chip8.is_halted());
+        assert_eq!(chip8.ram.V[0], 0);
+    }
+
+    #[test]
+    fn loads_long_index() {
+        // F000 1234 - LD I, 0x1234
+        let mut chip8 = Chip8::new(&[0xF0, 0x00, 0x12, 0x34], Quirks::xochip()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.I, 0x1234);
+        assert_eq!(chip8.ram.PC, Ram::START_PRGM_REGISTER + 4);
+    }
+
+    #[test]
+    fn skips_long_index_load() {
+        // 3000 F000 1234 - SE V0, 0; LD I, 0x1234
+        let program = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34];
+        let mut chip8 = Chip8::new(&program, Quirks::xochip()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.PC, Ram::START_PRGM_REGISTER + 6);
+    }
+
+    #[test]
+    fn saves_and_loads_register_ranges() {
+        // 6101 6202 6303 A300 5132 6100 6200 6300 5133
+        let program = [
+            0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00, 0x51, 0x32, 0x61, 0x00, 0x62, 0x00,
+            0x63, 0x00, 0x51, 0x33,
+        ];
+        let mut chip8 = Chip8::new(&program, Quirks::xochip()).unwrap();
+        (0..5).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.mem[0x300..0x303], [0x01, 0x02, 0x03]);
+        assert_eq!(chip8.ram.I, 0x300);
+
+        (0..4).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.V[1..4], [0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn selects_planes() {
+        // F201 - PLANE 2
+        let mut chip8 = Chip8::new(&[0xF2, 0x01], Quirks::xochip()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.planes, 2);
+    }
+
+    #[test]
+    fn ticks_timers_once_per_frame() {
+        // 603C F015 1204 - LD V0, 60; LD DT, V0; JP 0x204
+        let program = [0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+        let mut chip8 = Chip8::new(&program, Quirks::default()).unwrap();
+        chip8.scheduler.set_ips(600);
+
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.ram.delay_timer, 59);
+
+        (0..9).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.delay_timer, 59);
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.ram.delay_timer, 58);
+    }
+
+    #[test]
+    fn timer_speed_ignores_ips() {
+        // 603C F015 1204 - LD V0, 60; LD DT, V0; JP 0x204
+        let program = [0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04];
+        let mut slow = Chip8::new(&program, Quirks::default()).unwrap();
+        slow.scheduler.set_ips(300);
+        let mut fast = Chip8::new(&program, Quirks::default()).unwrap();
+        fast.scheduler.set_ips(3000);
+
+        for _ in 0..30 {
+            slow.run_frame().unwrap();
+            fast.run_frame().unwrap();
+        }
+
+        assert_eq!(slow.ram.delay_timer, 30);
+        assert_eq!(fast.ram.delay_timer, 30);
+    }
+
+    #[test]
+    fn reports_unknown_opcodes() {
+        // 6001 5121 - LD V0, 1; an undefined 5XYN
+        let mut chip8 = Chip8::new(&[0x60, 0x01, 0x51, 0x21], Quirks::default()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::UnknownOpcode {
+                pc: 0x202,
+                opcode: 0x5121
+            })
+        );
+        assert_eq!(chip8.ram.PC, 0x202);
+
+        chip8.skip_instruction();
+        assert_eq!(chip8.ram.PC, 0x204);
+    }
+
+    #[test]
+    fn reports_out_of_bounds_sprites() {
+        // D015 - DRW V0, V1, 5 with I two bytes from the end of memory
+        let mut chip8 = Chip8::new(&[0xD0, 0x15], Quirks::default()).unwrap();
+        chip8.ram.I = 0xFFFE;
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::MemoryOutOfBounds {
+                pc: 0x200,
+                addr: 0xFFFE,
+                len: 5
+            })
+        );
+    }
+
+    #[test]
+    fn sizes_memory_by_platform() {
+        // AFFE F355 - LD I, 0xFFE; LD [I], V3 - writes 0xFFE-0x1001
+        let program = [0xAF, 0xFE, 0xF3, 0x55];
+
+        let mut chip8 = Chip8::new(&program, Quirks::schip()).unwrap();
+        assert_eq!(chip8.ram.mem.len(), 0x1000);
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::MemoryOutOfBounds {
+                pc: 0x202,
+                addr: 0xFFE,
+                len: 4
+            })
+        );
+
+        let mut chip8 = Chip8::new(&program, Quirks::xochip()).unwrap();
+        assert_eq!(chip8.ram.mem.len(), 0x10000);
+        (0..2).for_each(|_| chip8.step().unwrap());
+    }
+
+    #[test]
+    fn reports_stack_underflow() {
+        let mut chip8 = Chip8::new(&[0x00, 0xEE], Quirks::default()).unwrap();
+        assert_eq!(chip8.step(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn reports_stack_overflow() {
+        // 2200 - CALL 0x200, forever
+        let mut chip8 = Chip8::new(&[0x22, 0x00], Quirks::default()).unwrap();
+        (0..Ram::STACK_SIZE).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+        assert_eq!(chip8.ram.stack.len(), Ram::STACK_SIZE);
+        assert_eq!(chip8.ram.PC, 0x200);
+    }
+
+    #[test]
+    fn masks_font_digit() {
+        // 60FF F029 - LD V0, 0xFF; LD F, V0
+        let mut chip8 = Chip8::new(&[0x60, 0xFF, 0xF0, 0x29], Quirks::default()).unwrap();
+        (0..2).for_each(|_| chip8.step().unwrap());
+        assert_eq!(chip8.ram.I as usize, Ram::FONT_START + 0xF * 5);
+    }
+
+    #[test]
+    fn runs_rom_headless() {
+        let mut chip8 = Chip8::new(IBM, Quirks::default()).unwrap();
+        for _ in 0..20 {
+            chip8.run_frame().unwrap();
+        }
+
+        assert!(chip8.take_draw_flag());
+        assert!(chip8
+            .display
+            .get_raw_bytes(&Display::PALETTE)
+            .iter()
+            .any(|&byte| byte != 0));
+    }
+}
@@ -0,0 +1,167 @@
+use clap::{Args as ClapArgs, Parser, Subcommand};
+
+use crate::debugger::command::Command as DebuggerCommand;
+use crate::emulator::{GameMode, Settings};
+use crate::error::ErrorPolicy;
+use crate::quirks::Quirks;
+
+/// A rustaceous chip8 emulator. Run without arguments for the interactive menu.
+#[derive(Debug, Parser)]
+#[command(name = "chippy", version, args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// a ROM to run with the default settings - the same as `chippy run <rom>`
+    pub rom: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a ROM without going through the menu
+    Run(RunArgs),
+    /// Assemble mnemonic source into a ROM
+    Asm {
+        /// path to the source
+        source: String,
+        /// where to write the ROM, the source path with a .ch8 extension by default
+        #[arg(short, long)]
+        output: Option<String>,
+        /// also write the labels to a symbol file, for the debugger and disassembler
+        #[arg(short, long)]
+        symbols: Option<String>,
+    },
+    /// List a ROM as mnemonics with addresses and labels
+    Disasm {
+        /// path to the ROM
+        rom: String,
+        /// name labels from a symbol file
+        #[arg(short, long)]
+        symbols: Option<String>,
+    },
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct RunArgs {
+    /// path to the ROM, or a URL to download it from
+    pub rom: String,
+    /// standard, or debug to start paused in the debugger
+    #[arg(short, long, default_value = "standard", value_parser = parse_mode)]
+    pub mode: GameMode,
+    /// instructions per second, from the config file or 700 by default
+    #[arg(long)]
+    pub ips: Option<u32>,
+    /// vip, chip48, schip or xochip, optionally followed by overrides (Ex. schip,wrap=true)
+    #[arg(short, long, value_parser = parse_quirks)]
+    pub quirks: Option<Quirks>,
+    /// window pixels per display pixel, from the config file or 10 by default
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: Option<u32>,
+    /// halt, skip or break on an unknown opcode or bad memory access, halt by default
+    #[arg(long, value_parser = parse_error_policy)]
+    pub on_error: Option<ErrorPolicy>,
+    /// fail unless the ROM has this SHA-1. A cached download that doesn't is downloaded again.
+    #[arg(long)]
+    pub sha1: Option<String>,
+    /// a debugger command to run before the game starts, Ex. --ex 'break 0x2A4'. Repeatable.
+    #[arg(long = "ex", value_name = "COMMAND", value_parser = parse_debugger_command)]
+    pub commands: Vec<DebuggerCommand>,
+}
+
+impl RunArgs {
+    /// base with the flags that were passed applied over it
+    pub fn settings(&self, base: Settings) -> Settings {
+        Settings {
+            game_mode: self.mode,
+            quirks: self.quirks.unwrap_or(base.quirks),
+            ips: self.ips.unwrap_or(base.ips),
+            error_policy: self.on_error.unwrap_or(base.error_policy),
+            scale: self.scale.unwrap_or(base.scale),
+            ..base
+        }
+    }
+}
+
+fn parse_mode(mode: &str) -> Result<GameMode, String> {
+    match mode.to_lowercase().as_str() {
+        "standard" => Ok(GameMode::Standard),
+        "debug" => Ok(GameMode::Debug),
+        _ => Err("expected standard or debug".to_string()),
+    }
+}
+
+fn parse_quirks(spec: &str) -> Result<Quirks, String> {
+    Quirks::from_spec(spec).map_err(|err| err.to_string())
+}
+
+fn parse_error_policy(policy: &str) -> Result<ErrorPolicy, String> {
+    policy.parse().map_err(|err: crate::Error| err.to_string())
+}
+
+fn parse_debugger_command(command: &str) -> Result<DebuggerCommand, String> {
+    command.parse().map_err(|err: crate::Error| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_run_flags() {
+        let args = Args::try_parse_from([
+            "chippy",
+            "run",
+            "games/Astro.ch8",
+            "--mode",
+            "debug",
+            "--ips",
+            "700",
+            "--quirks",
+            "schip,wrap=true",
+            "--scale",
+            "12",
+            "--ex",
+            "break 0x2A4",
+        ])
+        .unwrap();
+
+        let Some(Command::Run(run)) = args.command else {
+            panic!("expected run, got {:?}", args.command);
+        };
+        let settings = run.settings(Settings::default());
+        assert_eq!(run.rom, "games/Astro.ch8");
+        assert_eq!(settings.game_mode, GameMode::Debug);
+        assert_eq!(settings.ips, 700);
+        assert_eq!(settings.scale, 12);
+        assert!(settings.quirks.wrap_sprites);
+        assert!(!settings.quirks.load_store_increments_i);
+        assert_eq!(run.commands, ["break 0x2A4".parse().unwrap()]);
+    }
+
+    #[test]
+    fn takes_a_bare_rom() {
+        let args = Args::try_parse_from(["chippy", "games/Ibm.ch8"]).unwrap();
+        assert_eq!(args.rom.as_deref(), Some("games/Ibm.ch8"));
+        assert!(args.command.is_none());
+
+        // flags that aren't passed leave the config's settings alone
+        let Some(Command::Run(run)) = Args::try_parse_from(["chippy", "run", "x.ch8"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected run");
+        };
+        let base = Settings {
+            ips: 1000,
+            scale: 4,
+            error_policy: ErrorPolicy::Skip,
+            ..Settings::default()
+        };
+        let settings = run.settings(base);
+        assert_eq!((settings.ips, settings.scale), (1000, 4));
+        assert_eq!(settings.error_policy, ErrorPolicy::Skip);
+
+        assert!(Args::try_parse_from(["chippy"]).unwrap().rom.is_none());
+        assert!(Args::try_parse_from(["chippy", "run", "x.ch8", "--quirks", "nes"]).is_err());
+        assert!(Args::try_parse_from(["chippy", "run", "x.ch8", "--scale", "0"]).is_err());
+    }
+}
@@ -1,11 +1,12 @@
-use reqwest;
-
 use std::{fs, io};
 
 #[derive(Debug)]
 pub struct LocalGame();
 
-pub struct RemoteGame();
+/// the ROM's SHA-1 in hex - how saves and config overrides find it
+pub fn hash(program: &[u8]) -> String {
+    sha1_smol::Sha1::from(program).digest().to_string()
+}
 
 pub trait Loadable {
     type Res;
@@ -19,12 +20,3 @@ impl Loadable for LocalGame {
         fs::read(path)
     }
 }
-
-impl Loadable for RemoteGame {
-    type Res = std::result::Result<Vec<u8>, reqwest::Error>;
-
-    fn load(path: &str) -> Self::Res {
-        let resp = reqwest::blocking::get(path)?;
-        resp.bytes().map(|bytes| bytes.to_vec())
-    }
-}
@@ -1,2 +1,3 @@
+pub mod args;
 pub mod game;
 pub mod question;
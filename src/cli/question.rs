@@ -10,17 +10,9 @@ impl Question {
         prompt: Option<&str>,
         default: Option<&usize>,
     ) -> std::io::Result<Option<usize>> {
-        let default_idx = if let Some(&default_idx) = default {
-            default_idx
-        } else {
-            0
-        };
-
-        let prompt = if let Some(prompt) = prompt {
-            prompt
-        } else {
-            "Make a selection"
-        };
+        let default_idx = default.copied().unwrap_or(0);
+
+        let prompt = prompt.unwrap_or("Make a selection");
 
         Select::with_theme(&ColorfulTheme::default())
             .items(options)
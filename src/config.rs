@@ -0,0 +1,235 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::cli::game;
+use crate::emulator::Settings;
+use crate::quirks::Quirks;
+use crate::rom_db::RomDb;
+use crate::{Error, Result};
+
+/// Defaults for the settings a game runs with, from `chippy/config.toml`
+/// in the user's config directory. Every key is optional, Ex.
+///
+/// ```toml
+/// scale = 12
+/// ips = 1000
+/// quirks = "schip,wrap=true"
+/// # off, plane 1, plane 2, both planes
+/// colors = ["#000000", "#056E05", "#C8AA28", "#E6E6E6"]
+///
+/// [keymap]
+/// A = "Space"
+///
+/// [beep]
+/// frequency = 523.25
+/// volume = 0.1
+///
+/// # applied over the rest for the ROM with this SHA-1
+/// [rom.ac621d9fcada302ba6965768229ef130630bc525]
+/// quirks = "vip"
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    defaults: Overrides,
+    rom: HashMap<String, Overrides>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Overrides {
+    scale: Option<u32>,
+    ips: Option<u32>,
+    /// a preset optionally followed by overrides - see Quirks::from_spec
+    quirks: Option<String>,
+    /// "#RRGGBB" for each combination of the bitplanes, starting with off
+    colors: Vec<String>,
+    /// keypad key in hex -> SDL key name
+    keymap: BTreeMap<String, String>,
+    beep: BeepOverrides,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BeepOverrides {
+    frequency: Option<f32>,
+    volume: Option<f32>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chippy").join("config.toml"))
+    }
+
+    /// reads the config file, or the defaults if there isn't one
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => fs::read_to_string(&path)?
+                .parse()
+                .map_err(|err| format!("{}: {}", path.display(), err).into()),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// the settings for program - the defaults, then what the ROM database
+    /// knows about it, then its own section (if any)
+    pub fn settings(&self, program: &[u8]) -> Result<Settings> {
+        let mut settings = Settings::default();
+        self.defaults.apply(&mut settings)?;
+
+        let hash = game::hash(program);
+        if let Some(info) = RomDb::bundled().get(&hash) {
+            info.apply(&mut settings)?;
+        }
+        if let Some(overrides) = self.rom.get(&hash) {
+            overrides
+                .apply(&mut settings)
+                .map_err(|err| format!("[rom.{}] {}", hash, err))?;
+        }
+
+        Ok(settings)
+    }
+}
+
+impl Overrides {
+    fn apply(&self, settings: &mut Settings) -> Result<()> {
+        if let Some(scale) = self.scale {
+            if scale == 0 {
+                return Err("scale must be at least 1".into());
+            }
+            settings.scale = scale;
+        }
+        if let Some(ips) = self.ips {
+            settings.ips = ips;
+        }
+        if let Some(quirks) = &self.quirks {
+            settings.quirks = Quirks::from_spec(quirks)?;
+        }
+
+        if self.colors.len() > settings.palette.len() {
+            return Err(format!("expected at most {} colors", settings.palette.len()).into());
+        }
+        for (slot, color) in settings.palette.iter_mut().zip(&self.colors) {
+            *slot = parse_color(color)?;
+        }
+
+        for (key, name) in &self.keymap {
+            let hex = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&hex| hex <= 0xF)
+                .ok_or_else(|| format!("keymap: '{}' isn't a keypad key 0-F", key))?;
+            settings.keymap.insert(hex, name.clone());
+        }
+
+        if let Some(frequency) = self.beep.frequency {
+            if frequency <= 0.0 {
+                return Err("beep frequency must be above 0".into());
+            }
+            settings.beep.frequency = frequency;
+        }
+        if let Some(volume) = self.beep.volume {
+            if !(0.0..=1.0).contains(&volume) {
+                return Err("beep volume must be between 0 and 1".into());
+            }
+            settings.beep.volume = volume;
+        }
+
+        Ok(())
+    }
+}
+
+/// "#RRGGBB" -> [r, g, b, 0]
+fn parse_color(color: &str) -> Result<[u8; 4]> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .ok_or_else(|| format!("expected a color like #056E05, got '{}'", color))?;
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16);
+
+    Ok([channel(0)?, channel(2)?, channel(4)?, 0])
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut table: toml::value::Table = toml::from_str(text)?;
+        // split the ROM sections off so typos in the rest are still caught
+        let rom = match table.remove("rom") {
+            Some(rom) => rom.try_into()?,
+            None => HashMap::new(),
+        };
+
+        Ok(Config {
+            defaults: toml::Value::Table(table).try_into()?,
+            rom,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+
+    const CONFIG: &str = r##"
+scale = 12
+ips = 1000
+quirks = "schip,wrap=true"
+colors = ["#000000", "#FFB000"]
+
+[keymap]
+a = "Space"
+
+[beep]
+volume = 0.1
+
+[rom.da39a3ee5e6b4b0d3255bfef95601890afd80709]
+quirks = "vip"
+ips = 500
+"##;
+
+    #[test]
+    fn applies_defaults_then_rom_sections() {
+        let config = CONFIG.parse::<Config>().unwrap();
+
+        let settings = config.settings(&[0x00, 0xE0]).unwrap();
+        assert_eq!((settings.scale, settings.ips), (12, 1000));
+        assert_eq!(
+            settings.quirks,
+            Quirks::from_spec("schip,wrap=true").unwrap()
+        );
+        assert_eq!(settings.palette[1], [0xFF, 0xB0, 0x00, 0]);
+        assert_eq!(settings.palette[3], Display::PALETTE[3]);
+        assert_eq!(settings.keymap.get(&0xA).map(String::as_str), Some("Space"));
+        assert_eq!(settings.beep.volume, 0.1);
+        assert_eq!(settings.beep.frequency, 440.0);
+
+        // the SHA-1 of an empty ROM
+        let settings = config.settings(&[]).unwrap();
+        assert_eq!((settings.scale, settings.ips), (12, 500));
+        assert_eq!(settings.quirks, Quirks::cosmac_vip());
+    }
+
+    #[test]
+    fn rejects_bad_configs() {
+        for bad in [
+            "scael = 12",
+            "scale = 0",
+            "quirks = \"nes\"",
+            "colors = [\"green\"]",
+            "colors = [\"#000000\", \"#000000\", \"#000000\", \"#000000\", \"#000000\"]",
+            "[keymap]\nG = \"Space\"",
+            "[beep]\nvolume = 2.0",
+            "[rom.abc]\nips = \"fast\"",
+        ] {
+            let result = bad
+                .parse::<Config>()
+                .and_then(|config| config.settings(&[]));
+            assert!(result.is_err(), "{}", bad);
+        }
+        assert!(Config::default().settings(&[]).is_ok());
+    }
+}
@@ -0,0 +1,360 @@
+use std::{fmt, str::FromStr};
+
+use super::condition::Condition;
+use super::memory::Layout;
+use crate::chip8::Chip8;
+use crate::{Error, Result};
+
+/// A register, timer or memory byte the debugger can print, set or watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    V(u8),
+    I,
+    PC,
+    /// stack depth - read only
+    SP,
+    DT,
+    ST,
+    Mem(usize),
+}
+
+impl Target {
+    pub fn read(&self, chip8: &Chip8) -> usize {
+        let ram = &chip8.ram;
+        match *self {
+            Target::V(x) => ram.V[x as usize] as usize,
+            Target::I => ram.I as usize,
+            Target::PC => ram.PC,
+            Target::SP => ram.stack.len(),
+            Target::DT => ram.delay_timer as usize,
+            Target::ST => ram.sound_timer as usize,
+            Target::Mem(addr) => ram.mem.get(addr).copied().unwrap_or(0) as usize,
+        }
+    }
+
+    /// errors if the value doesn't fit the target
+    pub fn write(&self, chip8: &mut Chip8, value: usize) -> Result<()> {
+        let byte = || u8::try_from(value).map_err(|_| format!("{} holds a single byte", self));
+        let ram = &mut chip8.ram;
+
+        match *self {
+            Target::V(x) => ram.V[x as usize] = byte()?,
+            Target::I => {
+                ram.I = u16::try_from(value).map_err(|_| "I holds 16 bits".to_string())?;
+            }
+            Target::PC => {
+                if value >= ram.mem.len() {
+                    return Err(format!("{:#06X} is past the end of memory", value).into());
+                }
+                ram.PC = value;
+            }
+            Target::SP => return Err("SP is read only".into()),
+            Target::DT => ram.delay_timer = byte()?,
+            Target::ST => ram.sound_timer = byte()?,
+            Target::Mem(addr) => {
+                let value = byte()?;
+                *ram.mem
+                    .get_mut(addr)
+                    .ok_or_else(|| format!("{:#06X} is past the end of memory", addr))? = value;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::V(x) => write!(f, "V{:X}", x),
+            Target::I => write!(f, "I"),
+            Target::PC => write!(f, "PC"),
+            Target::SP => write!(f, "SP"),
+            Target::DT => write!(f, "DT"),
+            Target::ST => write!(f, "ST"),
+            Target::Mem(addr) => write!(f, "mem {:#06X}", addr),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    /// parses V0-VF, I, PC, SP, DT, ST or "mem <addr>"
+    fn from_str(target: &str) -> Result<Self> {
+        let target = target.trim();
+        if let Some(addr) = target.strip_prefix("mem ") {
+            return Ok(Target::Mem(parse_number(addr)?));
+        }
+
+        match target.to_uppercase().as_str() {
+            "I" => Ok(Target::I),
+            "PC" => Ok(Target::PC),
+            "SP" => Ok(Target::SP),
+            "DT" => Ok(Target::DT),
+            "ST" => Ok(Target::ST),
+            register => register
+                .strip_prefix('V')
+                .filter(|x| x.len() == 1)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .map(Target::V)
+                .ok_or_else(|| format!("unknown register '{}'", target).into()),
+        }
+    }
+}
+
+/// A line typed at the debugger prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Break(usize, Option<Condition>),
+    /// removes a breakpoint
+    Delete(usize),
+    Watch(Target, Option<Condition>),
+    /// removes a watchpoint
+    Unwatch(Target),
+    Step(usize),
+    Continue,
+    Print(Target),
+    /// shows len bytes of memory from addr
+    Examine {
+        addr: usize,
+        len: usize,
+        layout: Layout,
+    },
+    Set(Target, usize),
+    /// logs every instruction to a file, or stops logging when None
+    Trace(Option<String>),
+    /// serves the GDB remote protocol on a local port
+    Gdb(u16),
+    /// loads a symbol file
+    Symbols(String),
+    /// lists the call stack
+    Backtrace,
+    /// starts profiling - the report is saved to the file, or printed when None
+    Profile(Option<String>),
+    /// stops profiling and reports
+    ProfileOff,
+    Registers,
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub const HELP: &'static str = "\
+break <addr>          stop before executing the instruction at addr
+delete <addr>         remove a breakpoint
+watch <target>        stop when a target changes (Ex. watch V3, watch mem 0x300)
+break/watch ... if <condition>
+                      only stop when the condition holds as well, Ex.
+                      break 0x2A4 if V3 == 0x10 && mem[I + 1] != 0
+unwatch <target>      remove a watchpoint
+step [count]          execute count instructions, 1 by default
+continue              run until the next breakpoint or watchpoint
+print <target>        print V0-VF, I, PC, SP, DT, ST or mem <addr>
+x/<len> <addr>        dump len bytes of memory, 16 by default
+x/<len>s <addr>       dump len bytes of memory as sprite rows
+set <target> = <val>  change a register, timer or memory byte
+trace <file>          log every executed instruction to file
+trace off             stop logging
+gdb [port]            wait for a GDB client on localhost, port 1234 by default
+symbols <file>        name addresses from a symbol file (Ex. main_loop+0x4)
+backtrace             list PC and the return address of every call on the stack
+profile [file]        count executed instructions, reported when the game exits
+                      (saved to file as .csv or .json, printed otherwise)
+profile off           stop profiling and report now
+regs                  print every register
+help                  print this message
+quit                  stop the game
+An empty line repeats the last command.";
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+
+        let command = match name {
+            "break" | "b" => {
+                let (addr, condition) = split_condition(args)?;
+                Command::Break(parse_number(addr)?, condition)
+            }
+            "delete" | "d" => Command::Delete(parse_number(args)?),
+            "watch" | "w" => {
+                let (target, condition) = split_condition(args)?;
+                Command::Watch(target.parse()?, condition)
+            }
+            "unwatch" => Command::Unwatch(args.parse()?),
+            "step" | "s" => Command::Step(if args.is_empty() {
+                1
+            } else {
+                parse_number(args)?
+            }),
+            "continue" | "c" => Command::Continue,
+            "print" | "p" => Command::Print(args.parse()?),
+            "set" => {
+                let (target, value) = args
+                    .split_once('=')
+                    .ok_or("expected set <target> = <value>")?;
+                Command::Set(target.parse()?, parse_number(value)?)
+            }
+            "trace" => match args {
+                "" => return Err("expected trace <file> or trace off".into()),
+                "off" => Command::Trace(None),
+                path => Command::Trace(Some(path.to_string())),
+            },
+            "gdb" => Command::Gdb(if args.is_empty() {
+                1234
+            } else {
+                u16::try_from(parse_number(args)?).map_err(|_| "port out of range")?
+            }),
+            "symbols" if args.is_empty() => return Err("expected symbols <file>".into()),
+            "symbols" => Command::Symbols(args.to_string()),
+            "backtrace" | "bt" => Command::Backtrace,
+            "profile" => match args {
+                "" => Command::Profile(None),
+                "off" => Command::ProfileOff,
+                path => Command::Profile(Some(path.to_string())),
+            },
+            "regs" => Command::Registers,
+            "help" | "h" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            _ if name == "x" || name.starts_with("x/") => {
+                let format = name.strip_prefix("x/").unwrap_or("");
+                let (len, layout) = match format.strip_suffix('s') {
+                    Some(len) => (len, Layout::Sprite),
+                    None => (format, Layout::Hex),
+                };
+                Command::Examine {
+                    addr: parse_number(args)?,
+                    len: if len.is_empty() {
+                        16
+                    } else {
+                        parse_number(len)?
+                    },
+                    layout,
+                }
+            }
+            _ => return Err(format!("unknown command '{}' - try help", name).into()),
+        };
+
+        Ok(command)
+    }
+}
+
+/// splits "<args> if <condition>"
+fn split_condition(args: &str) -> Result<(&str, Option<Condition>)> {
+    match args.split_once(" if ") {
+        Some((args, condition)) => Ok((args, Some(condition.parse()?))),
+        None => Ok((args, None)),
+    }
+}
+
+/// parses 0x prefixed hex or decimal
+pub fn parse_number(number: &str) -> Result<usize> {
+    let number = number.trim();
+    let parsed = match number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => number.parse(),
+    };
+
+    parsed.map_err(|_| format!("expected a number, got '{}'", number).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            "break 0x2A4".parse::<Command>().unwrap(),
+            Command::Break(0x2A4, None)
+        );
+        assert_eq!(
+            "b 0x2A4 if DT == 0".parse::<Command>().unwrap(),
+            Command::Break(0x2A4, Some("DT == 0".parse().unwrap()))
+        );
+        assert!("b 0x2A4 if DT ==".parse::<Command>().is_err());
+        assert_eq!(
+            "watch V3".parse::<Command>().unwrap(),
+            Command::Watch(Target::V(3), None)
+        );
+        assert_eq!(
+            "watch mem 0x300".parse::<Command>().unwrap(),
+            Command::Watch(Target::Mem(0x300), None)
+        );
+        assert_eq!("step 10".parse::<Command>().unwrap(), Command::Step(10));
+        assert_eq!("gdb".parse::<Command>().unwrap(), Command::Gdb(1234));
+        assert_eq!(
+            "symbols pong.sym".parse::<Command>().unwrap(),
+            Command::Symbols("pong.sym".to_string())
+        );
+        assert_eq!("bt".parse::<Command>().unwrap(), Command::Backtrace);
+        assert_eq!(
+            "profile out.csv".parse::<Command>().unwrap(),
+            Command::Profile(Some("out.csv".to_string()))
+        );
+        assert_eq!(
+            "profile off".parse::<Command>().unwrap(),
+            Command::ProfileOff
+        );
+        assert!("gdb 70000".parse::<Command>().is_err());
+        assert_eq!("step".parse::<Command>().unwrap(), Command::Step(1));
+        assert_eq!("continue".parse::<Command>().unwrap(), Command::Continue);
+        assert_eq!(
+            "print I".parse::<Command>().unwrap(),
+            Command::Print(Target::I)
+        );
+        assert_eq!(
+            "x/16 0x300".parse::<Command>().unwrap(),
+            Command::Examine {
+                addr: 0x300,
+                len: 16,
+                layout: Layout::Hex
+            }
+        );
+        assert_eq!(
+            "x/5s 0x50".parse::<Command>().unwrap(),
+            Command::Examine {
+                addr: 0x50,
+                len: 5,
+                layout: Layout::Sprite
+            }
+        );
+        assert_eq!(
+            "set V5 = 0x10".parse::<Command>().unwrap(),
+            Command::Set(Target::V(5), 0x10)
+        );
+        assert_eq!(
+            "trace out.log".parse::<Command>().unwrap(),
+            Command::Trace(Some("out.log".to_string()))
+        );
+        assert_eq!(
+            "trace off".parse::<Command>().unwrap(),
+            Command::Trace(None)
+        );
+        assert!("set V5 0x10".parse::<Command>().is_err());
+        assert!("watch VG".parse::<Command>().is_err());
+        assert!("jump".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn reads_and_writes_targets() {
+        let mut chip8 = Chip8::new(&[], Quirks::default()).unwrap();
+
+        Target::V(5).write(&mut chip8, 0x10).unwrap();
+        assert_eq!(chip8.ram.V[5], 0x10);
+        Target::Mem(0x300).write(&mut chip8, 0xAB).unwrap();
+        assert_eq!(Target::Mem(0x300).read(&chip8), 0xAB);
+
+        assert!(Target::V(5).write(&mut chip8, 0x100).is_err());
+        assert!(Target::SP.write(&mut chip8, 1).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fmt, str::FromStr};
+
+use super::command::{parse_number, Target};
+use crate::chip8::Chip8;
+use crate::{Error, Result};
+
+/// A boolean expression over the machine state, for conditional
+/// breakpoints and watchpoints, Ex. `V3 == 0x10 && I > 0x300`.
+///
+/// Operands are numbers, V0-VF, I, PC, SP, DT, ST and `mem[<expr>]`.
+/// Operators from loosest to tightest: `||`, `&&`, comparisons (`==`, `!=`,
+/// `<`, `<=`, `>`, `>=`), `+` and `-`, then `!` and parentheses. Everything
+/// evaluates to a number, with comparisons giving 1 or 0 and anything but 0
+/// counting as true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(usize),
+    Target(Target),
+    Mem(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+impl Condition {
+    /// true if the condition holds for chip8 as it is now
+    pub fn eval(&self, chip8: &Chip8) -> bool {
+        self.expr.eval(chip8) != 0
+    }
+}
+
+impl Expr {
+    fn eval(&self, chip8: &Chip8) -> usize {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Target(target) => target.read(chip8),
+            Expr::Mem(addr) => Target::Mem(addr.eval(chip8)).read(chip8),
+            Expr::Not(expr) => (expr.eval(chip8) == 0) as usize,
+            Expr::Binary(lhs, op, rhs) => {
+                let lhs = lhs.eval(chip8);
+                // short circuit so mem[] on the right isn't read needlessly
+                match op {
+                    Op::Or if lhs != 0 => return 1,
+                    Op::And if lhs == 0 => return 0,
+                    _ => (),
+                }
+                let rhs = rhs.eval(chip8);
+
+                match op {
+                    Op::Or | Op::And => (rhs != 0) as usize,
+                    Op::Eq => (lhs == rhs) as usize,
+                    Op::Ne => (lhs != rhs) as usize,
+                    Op::Lt => (lhs < rhs) as usize,
+                    Op::Le => (lhs <= rhs) as usize,
+                    Op::Gt => (lhs > rhs) as usize,
+                    Op::Ge => (lhs >= rhs) as usize,
+                    Op::Add => lhs.wrapping_add(rhs),
+                    Op::Sub => lhs.wrapping_sub(rhs),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected '{}'", token).into());
+        }
+
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+/// splits source into numbers, names and operators
+fn tokenize(source: &str) -> Result<Vec<String>> {
+    const OPERATORS: [&str; 15] = [
+        "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "!", "(", ")", "[", "]",
+    ];
+
+    let mut tokens = vec![];
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let len = if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            op.len()
+        } else {
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len())
+        };
+
+        if len == 0 {
+            let unexpected = rest.chars().next().unwrap_or_default();
+            return Err(format!("unexpected '{}'", unexpected).into());
+        }
+
+        tokens.push(rest[..len].to_string());
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// recursive descent, a method per precedence level
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("expression ends early")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected '{}', got '{}'", expected, token).into()),
+        }
+    }
+
+    /// parses a run of operands at the next level down joined by ops
+    fn binary(
+        &mut self,
+        ops: &[(&str, Op)],
+        operand: fn(&mut Self) -> Result<Expr>,
+        chain: bool,
+    ) -> Result<Expr> {
+        let mut lhs = operand(self)?;
+
+        while let Some(&(_, op)) = ops.iter().find(|(token, _)| self.peek() == Some(token)) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(operand(self)?));
+
+            // V0 < V1 < V2 doesn't mean what it looks like
+            if !chain {
+                break;
+            }
+        }
+
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        self.binary(&[("||", Op::Or)], Self::and, true)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        self.binary(&[("&&", Op::And)], Self::comparison, true)
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        self.binary(&ops, Self::sum, false)
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        self.binary(&[("+", Op::Add), ("-", Op::Sub)], Self::unary, true)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self.next()?;
+
+        match token.as_str() {
+            "!" => Ok(Expr::Not(Box::new(self.unary()?))),
+            "(" => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ if token.eq_ignore_ascii_case("mem") => {
+                self.expect("[")?;
+                let addr = self.or()?;
+                self.expect("]")?;
+                Ok(Expr::Mem(Box::new(addr)))
+            }
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(Expr::Number(parse_number(&token)?))
+            }
+            _ => Ok(Expr::Target(token.parse()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    fn eval(condition: &str, chip8: &Chip8) -> bool {
+        condition.parse::<Condition>().unwrap().eval(chip8)
+    }
+
+    #[test]
+    fn evaluates_against_the_machine() {
+        let mut chip8 = Chip8::new(&[0x00, 0xE0], Quirks::default()).unwrap();
+        chip8.ram.V[3] = 0x10;
+        chip8.ram.I = 0x3F0;
+        chip8.ram.mem[0x3F1] = 7;
+
+        assert!(eval("V3 == 0x10 && I > 0x300", &chip8));
+        assert!(eval("DT == 0", &chip8));
+        assert!(eval("mem[0x3F0] != 0 || mem[I + 1] == 7", &chip8));
+        assert!(eval("!(v3 < 16) && (PC >= 0x200)", &chip8));
+        assert!(!eval("V3 == 0x10 && ST", &chip8));
+        assert!(!eval("mem[0xFFFFF]", &chip8));
+    }
+
+    #[test]
+    fn keeps_precedence() {
+        let chip8 = Chip8::new(&[0x00, 0xE0], Quirks::default()).unwrap();
+
+        // && binds tighter than ||
+        assert!(eval("1 || 0 && 0", &chip8));
+        // - binds tighter than ==
+        assert!(eval("5 - 2 == 3", &chip8));
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        for bad in [
+            "",
+            "V3 ==",
+            "V3 == 0x10)",
+            "VG == 1",
+            "mem 0x300",
+            "V1 < V2 < V3",
+            "V1 $ 2",
+            "V3 == é",
+            "日本 == 1",
+        ] {
+            assert!(bad.parse::<Condition>().is_err(), "{}", bad);
+        }
+        assert_eq!(
+            "V3 == é".parse::<Condition>().unwrap_err().to_string(),
+            "unexpected 'é'"
+        );
+        assert_eq!(
+            " V3 == 0x10 ".parse::<Condition>().unwrap().to_string(),
+            "V3 == 0x10"
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use super::command::Target;
+use super::Debugger;
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Registers in GDB's numbering. Multi-byte registers go over the wire
+/// little endian, as the `g` packet concatenates them.
+const REGISTERS: [(Target, usize); 21] = [
+    (Target::V(0x0), 1),
+    (Target::V(0x1), 1),
+    (Target::V(0x2), 1),
+    (Target::V(0x3), 1),
+    (Target::V(0x4), 1),
+    (Target::V(0x5), 1),
+    (Target::V(0x6), 1),
+    (Target::V(0x7), 1),
+    (Target::V(0x8), 1),
+    (Target::V(0x9), 1),
+    (Target::V(0xA), 1),
+    (Target::V(0xB), 1),
+    (Target::V(0xC), 1),
+    (Target::V(0xD), 1),
+    (Target::V(0xE), 1),
+    (Target::V(0xF), 1),
+    (Target::I, 2),
+    (Target::PC, 2),
+    (Target::SP, 1),
+    (Target::DT, 1),
+    (Target::ST, 1),
+];
+
+/// GDB remote serial protocol server on a local TCP port.
+///
+/// The stub never blocks - the frontend calls `poll` every time round its
+/// loop to accept a client and answer its packets, and stops running frames
+/// while `is_halted`. Breakpoints are the Debugger's, so ones set from GDB
+/// and from the debugger prompt are the same.
+///
+/// Registers are V0-VF, I, PC, SP (stack depth, read only), DT and ST, in
+/// that order - see REGISTERS. The layout is also served as target.xml.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    /// bytes received but not yet handled
+    inbox: Vec<u8>,
+    /// true while the client has the machine stopped
+    halted: bool,
+}
+
+impl GdbStub {
+    /// listens on localhost. Port 0 picks any free port.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(GdbStub {
+            listener,
+            client: None,
+            inbox: vec![],
+            halted: true,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// true from binding until a client continues, and whenever the client
+    /// stops the machine after that
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// accepts a client if there's none and answers whatever it has sent
+    pub fn poll(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(stream);
+                    self.halted = true;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.receive()?;
+
+        while let Some(packet) = self.next_packet()? {
+            if let Some(reply) = self.handle(&packet, chip8, debugger) {
+                self.send(&reply)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// tells the client the machine stopped on its own (Ex. at a breakpoint)
+    pub fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.halted = true;
+        self.send(&format!("S{:02x}", signal))
+    }
+
+    /// tells the client the program exited with 00FD
+    pub fn exited(&mut self) -> io::Result<()> {
+        self.halted = true;
+        self.send("W00")
+    }
+
+    /// the signal a client sees for err
+    pub fn signal(err: &Chip8Error) -> u8 {
+        match err {
+            Chip8Error::UnknownOpcode { .. } => SIGILL,
+            _ => SIGSEGV,
+        }
+    }
+
+    /// the signal a client sees for a debugger stop
+    pub fn trap() -> u8 {
+        SIGTRAP
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+
+        let mut buf = [0; 1024];
+        loop {
+            match client.read(&mut buf) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Ok(len) => self.inbox.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => {
+                    self.disconnect();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// pulls the next complete packet out of the inbox, acking it. A Ctrl-C
+    /// from the client comes back as the packet "\x03".
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.inbox.first() {
+                None => return Ok(None),
+                Some(0x03) => {
+                    self.inbox.remove(0);
+                    return Ok(Some("\x03".to_string()));
+                }
+                Some(b'$') => break,
+                // acks, nacks and line noise
+                Some(_) => {
+                    self.inbox.remove(0);
+                }
+            }
+        }
+
+        let Some(end) = self.inbox.iter().position(|&byte| byte == b'#') else {
+            return Ok(None);
+        };
+        if self.inbox.len() < end + 3 {
+            return Ok(None);
+        }
+
+        let frame = self.inbox.drain(..end + 3).collect::<Vec<u8>>();
+        let data = &frame[1..end];
+        let checksum = std::str::from_utf8(&frame[end + 1..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        if checksum != Some(checksum_of(data)) {
+            self.write_raw(b"-")?;
+            return Ok(None);
+        }
+
+        self.write_raw(b"+")?;
+        Ok(Some(String::from_utf8_lossy(data).into_owned()))
+    }
+
+    /// answers a packet. None means no reply yet (Ex. continue).
+    fn handle(
+        &mut self,
+        packet: &str,
+        chip8: &mut Chip8,
+        debugger: &mut Debugger,
+    ) -> Option<String> {
+        let reply = match packet.as_bytes().first() {
+            Some(0x03) => {
+                self.halted = true;
+                format!("S{:02x}", SIGINT)
+            }
+            Some(b'?') => format!("S{:02x}", SIGTRAP),
+            Some(b'g') => REGISTERS
+                .iter()
+                .map(|&(target, size)| encode_le(target.read(chip8), size))
+                .collect(),
+            Some(b'G') => write_registers(&packet[1..], chip8),
+            Some(b'p') => usize::from_str_radix(&packet[1..], 16)
+                .ok()
+                .and_then(|n| REGISTERS.get(n))
+                .map_or_else(error, |&(target, size)| encode_le(target.read(chip8), size)),
+            Some(b'P') => write_register(&packet[1..], chip8),
+            Some(b'm') => read_memory(&packet[1..], chip8),
+            Some(b'M') => write_memory(&packet[1..], chip8),
+            Some(b'c') => {
+                self.halted = false;
+                return None;
+            }
+            Some(b's') => match debugger.step(chip8, 1) {
+                Ok(_) => format!("S{:02x}", SIGTRAP),
+                Err(err) => format!("S{:02x}", Self::signal(&err)),
+            },
+            Some(b'Z') | Some(b'z') => {
+                let insert = packet.starts_with('Z');
+                match parse_breakpoint(&packet[1..]) {
+                    Some(addr) if insert => {
+                        debugger.add_breakpoint(addr, None);
+                        "OK".to_string()
+                    }
+                    Some(addr) => {
+                        debugger.remove_breakpoint(addr);
+                        "OK".to_string()
+                    }
+                    // only software breakpoints are supported
+                    None => String::new(),
+                }
+            }
+            Some(b'D') => {
+                self.send("OK").ok();
+                self.disconnect();
+                return None;
+            }
+            Some(b'k') => {
+                chip8.halt();
+                self.disconnect();
+                return None;
+            }
+            Some(b'H') => "OK".to_string(),
+            Some(b'q') => query(packet),
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.inbox.clear();
+        self.halted = false;
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let frame = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.write_raw(frame.as_bytes())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+
+        // the socket is non-blocking, but replies are small enough that
+        // WouldBlock only happens if the client stopped reading
+        client.set_nonblocking(false)?;
+        let written = client.write_all(bytes);
+        client.set_nonblocking(true)?;
+        written
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn encode_le(value: usize, size: usize) -> String {
+    value.to_le_bytes()[..size]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_le(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as usize)
+}
+
+/// "addr,len" in hex
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (addr, len) = range.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// "0,addr,kind" - type 0 is a software breakpoint
+fn parse_breakpoint(args: &str) -> Option<usize> {
+    let mut fields = args.split(',');
+    if fields.next()? != "0" {
+        return None;
+    }
+    usize::from_str_radix(fields.next()?, 16).ok()
+}
+
+fn write_registers(hex: &str, chip8: &mut Chip8) -> String {
+    let Some(bytes) = decode_hex(hex) else {
+        return error();
+    };
+
+    let mut offset = 0;
+    for &(target, size) in REGISTERS.iter() {
+        let Some(value) = bytes.get(offset..offset + size) else {
+            break;
+        };
+        offset += size;
+
+        // SP is read only - leave it be as long as the client didn't change it
+        let value = decode_le(value);
+        if target == Target::SP && value == target.read(chip8) {
+            continue;
+        }
+        if target.write(chip8, value).is_err() {
+            return error();
+        }
+    }
+
+    "OK".to_string()
+}
+
+fn write_register(args: &str, chip8: &mut Chip8) -> String {
+    let register = args.split_once('=').and_then(|(n, value)| {
+        let &(target, size) = REGISTERS.get(usize::from_str_radix(n, 16).ok()?)?;
+        let bytes = decode_hex(value).filter(|bytes| bytes.len() == size)?;
+        Some((target, decode_le(&bytes)))
+    });
+
+    match register {
+        Some((target, value)) if target.write(chip8, value).is_ok() => "OK".to_string(),
+        _ => error(),
+    }
+}
+
+fn read_memory(range: &str, chip8: &Chip8) -> String {
+    let mem = &chip8.ram.mem;
+    match parse_range(range) {
+        Some((addr, len)) if addr < mem.len() => mem[addr..addr.saturating_add(len).min(mem.len())]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        _ => error(),
+    }
+}
+
+fn write_memory(args: &str, chip8: &mut Chip8) -> String {
+    let write = args.split_once(':').and_then(|(range, hex)| {
+        let (addr, len) = parse_range(range)?;
+        let bytes = decode_hex(hex).filter(|bytes| bytes.len() == len)?;
+        chip8
+            .ram
+            .mem
+            .get_mut(addr..addr.checked_add(len)?)
+            .map(|mem| mem.copy_from_slice(&bytes))
+    });
+
+    write.map_or_else(error, |_| "OK".to_string())
+}
+
+fn query(packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if packet == "qAttached" {
+        "1".to_string()
+    } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        let xml = target_xml();
+        match parse_range(range) {
+            Some((offset, len)) if offset <= xml.len() => {
+                let end = offset.saturating_add(len).min(xml.len());
+                let more = if end < xml.len() { 'm' } else { 'l' };
+                format!("{}{}", more, &xml[offset..end])
+            }
+            _ => error(),
+        }
+    } else {
+        String::new()
+    }
+}
+
+fn target_xml() -> String {
+    let registers = REGISTERS
+        .iter()
+        .map(|&(target, size)| {
+            let kind = match target {
+                Target::I => " type=\"data_ptr\"",
+                Target::PC => " type=\"code_ptr\"",
+                _ => "",
+            };
+            format!(
+                "<reg name=\"{}\" bitsize=\"{}\"{}/>",
+                target.to_string().to_lowercase(),
+                size * 8,
+                kind
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chippy.chip8\">{}</feature></target>",
+        registers
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::Stop;
+    use crate::quirks::Quirks;
+    use std::time::Duration;
+
+    // 6A2F 1202 - LD VA, 0x2F; JP 0x202
+    const PROGRAM: &[u8] = &[0x6A, 0x2F, 0x12, 0x02];
+
+    /// sends a packet and polls the stub until it replies
+    fn request(
+        client: &mut TcpStream,
+        stub: &mut GdbStub,
+        chip8: &mut Chip8,
+        debugger: &mut Debugger,
+        data: &str,
+    ) -> String {
+        let frame = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        client.write_all(frame.as_bytes()).unwrap();
+
+        let mut received = vec![];
+        let mut buf = [0; 1024];
+        loop {
+            stub.poll(chip8, debugger).unwrap();
+            if let Ok(len) = client.read(&mut buf) {
+                received.extend_from_slice(&buf[..len]);
+            }
+
+            let text = String::from_utf8_lossy(&received).into_owned();
+            if let Some(start) = text.find('$') {
+                if let Some(end) = text[start..].find('#') {
+                    if text.len() >= start + end + 3 {
+                        return text[start + 1..start + end].to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn serves_registers_memory_and_breakpoints() {
+        let mut stub = GdbStub::bind(0).unwrap();
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+
+        let mut client = TcpStream::connect(stub.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut ask = |data: &str, chip8: &mut Chip8, debugger: &mut Debugger| {
+            request(&mut client, &mut stub, chip8, debugger, data)
+        };
+
+        assert_eq!(ask("?", &mut chip8, &mut debugger), "S05");
+        assert_eq!(ask("s", &mut chip8, &mut debugger), "S05");
+
+        let registers = ask("g", &mut chip8, &mut debugger);
+        // V0-V9 then VA, with I after VF and PC after that
+        assert_eq!(&registers[20..22], "2f");
+        assert_eq!(&registers[36..40], "0202");
+
+        assert_eq!(ask("P5=10", &mut chip8, &mut debugger), "OK");
+        assert_eq!(chip8.ram.V[5], 0x10);
+        assert_eq!(ask("p11", &mut chip8, &mut debugger), "0202");
+
+        assert_eq!(ask("m200,2", &mut chip8, &mut debugger), "6a2f");
+        assert_eq!(ask("M300,2:abcd", &mut chip8, &mut debugger), "OK");
+        assert_eq!(chip8.ram.mem[0x300..0x302], [0xAB, 0xCD]);
+
+        assert_eq!(ask("Z0,202,2", &mut chip8, &mut debugger), "OK");
+        assert_eq!(
+            debugger.step(&mut chip8, 5),
+            Ok(Some(Stop::Breakpoint(0x202)))
+        );
+        assert_eq!(ask("z0,202,2", &mut chip8, &mut debugger), "OK");
+        assert_eq!(ask("vMustReplyEmpty", &mut chip8, &mut debugger), "");
+    }
+
+    #[test]
+    fn rejects_oversized_ranges() {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let max = "ffffffffffffffff";
+
+        // a huge length from a valid address reads up to the end of memory
+        assert_eq!(
+            read_memory(&format!("1,{}", max), &chip8).len(),
+            (chip8.ram.mem.len() - 1) * 2
+        );
+        assert_eq!(read_memory(&format!("{},{}", max, max), &chip8), "E01");
+        assert_eq!(read_memory(&format!("{},1", max), &chip8), "E01");
+
+        assert_eq!(write_memory(&format!("{},1:00", max), &mut chip8), "E01");
+        assert_eq!(write_memory(&format!("0,{}:00", max), &mut chip8), "E01");
+
+        let xfer = "qXfer:features:read:target.xml:";
+        assert!(query(&format!("{}1,{}", xfer, max)).starts_with("l?xml"));
+        assert_eq!(query(&format!("{}{},{}", xfer, max, max)), "E01");
+    }
+
+    #[test]
+    fn decodes_registers_little_endian() {
+        assert_eq!(encode_le(0x0202, 2), "0202");
+        assert_eq!(encode_le(0x1234, 2), "3412");
+        assert_eq!(decode_le(&[0x34, 0x12]), 0x1234);
+        assert!(target_xml().contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+    }
+}
@@ -0,0 +1,109 @@
+use console::style;
+
+/// How the memory viewer lays out bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 16 bytes a line with an ASCII column
+    Hex,
+    /// a byte a line with its ASCII character and its pixels as a sprite row
+    Sprite,
+}
+
+/// Renders len bytes of mem from addr. Bytes that differ from marked - a
+/// copy of memory from earlier - are highlighted. Sprite rows use the same
+/// `#` and `.` pixels the assembler's sprite literals do.
+pub fn view(mem: &[u8], marked: &[u8], addr: usize, len: usize, layout: Layout) -> String {
+    let start = addr.min(mem.len());
+    let end = addr.saturating_add(len).min(mem.len());
+    let per_line = match layout {
+        Layout::Hex => 16,
+        Layout::Sprite => 1,
+    };
+
+    let changed = |addr: usize| marked.get(addr).is_some_and(|&old| old != mem[addr]);
+    let highlight = |text: String, addr: usize| {
+        if changed(addr) {
+            style(text).black().on_yellow().to_string()
+        } else {
+            text
+        }
+    };
+
+    mem[start..end]
+        .chunks(per_line)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let line_addr = start + line * per_line;
+            let addrs = line_addr..line_addr + bytes.len();
+
+            let hex = bytes
+                .iter()
+                .zip(addrs.clone())
+                .map(|(byte, addr)| highlight(format!("{:02X}", byte), addr))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let ascii = bytes.iter().map(|&byte| ascii(byte)).collect::<String>();
+
+            match layout {
+                Layout::Hex => {
+                    // pad a short last line so the ASCII column lines up
+                    let padding = "   ".repeat(per_line - bytes.len());
+                    format!("{:#06X}: {}{}  |{}|", line_addr, hex, padding, ascii)
+                }
+                Layout::Sprite => {
+                    let pixels = highlight(sprite_row(bytes[0]), line_addr);
+                    format!("{:#06X}: {}  {}  {}", line_addr, hex, ascii, pixels)
+                }
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn ascii(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+fn sprite_row(byte: u8) -> String {
+    (0..8)
+        .rev()
+        .map(|bit| if byte >> bit & 1 == 1 { '#' } else { '.' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_hex_and_ascii() {
+        let mem = b"Hi\x00\xF0".to_vec();
+        assert_eq!(
+            view(&mem, &mem, 0, 4, Layout::Hex),
+            format!("0x0000: 48 69 00 F0{}  |Hi..|", "   ".repeat(12))
+        );
+    }
+
+    #[test]
+    fn lays_out_sprites() {
+        let mem = [0xF0, 0x90];
+        assert_eq!(
+            view(&mem, &mem, 0, 2, Layout::Sprite),
+            "0x0000: F0  .  ####....\n0x0001: 90  .  #..#...."
+        );
+    }
+
+    #[test]
+    fn highlights_changed_bytes() {
+        console::set_colors_enabled(true);
+        let marked = [0x00, 0x00];
+        let highlighted = view(&[0x00, 0x01], &marked, 0, 2, Layout::Hex);
+
+        assert!(highlighted.contains(&style("01".to_string()).black().on_yellow().to_string()));
+        assert!(highlighted.starts_with("0x0000: 00 "));
+    }
+}
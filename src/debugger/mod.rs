@@ -0,0 +1,425 @@
+pub mod command;
+pub mod condition;
+pub mod gdb;
+pub mod memory;
+pub mod overlay;
+pub mod profiler;
+pub mod trace;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::symbols::Symbols;
+use command::Target;
+use condition::Condition;
+use memory::Layout;
+use profiler::Profiler;
+use trace::Trace;
+
+/// Breakpoints and watchpoints over a Chip8.
+///
+/// Breakpoints are checked after every instruction, so execution stops
+/// with PC on the breakpoint before it runs. Cycles spent waiting on FX0A
+/// execute nothing and are never checked, so continuing off a breakpoint
+/// doesn't stop on it again until it has run.
+#[derive(Default)]
+pub struct Debugger {
+    /// each breakpoint's address and the condition it only stops when
+    breakpoints: BTreeMap<usize, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    trace: Option<Trace>,
+    profiler: Option<Profiler>,
+    /// memory as it was when execution last resumed
+    marked: Vec<u8>,
+    symbols: Symbols,
+}
+
+struct Watchpoint {
+    target: Target,
+    /// the value it had after the last instruction
+    value: usize,
+    /// only stops when this holds as well as the target changing
+    condition: Option<Condition>,
+}
+
+/// Why the debugger stopped execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(usize),
+    Watchpoint {
+        target: Target,
+        old: usize,
+        new: usize,
+    },
+    /// writing the trace failed - tracing is switched off
+    TraceFailed(String),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(addr) => write!(f, "Breakpoint at {:#06X}", addr),
+            Stop::Watchpoint { target, old, new } => {
+                write!(f, "Watchpoint {} changed {:#X} -> {:#X}", target, old, new)
+            }
+            Stop::TraceFailed(err) => write!(f, "Writing the trace failed - {}", err),
+        }
+    }
+}
+
+impl Debugger {
+    /// stops before addr runs, as long as condition (if any) holds then.
+    /// Returns false if there was already a breakpoint at addr - its
+    /// condition is replaced.
+    pub fn add_breakpoint(&mut self, addr: usize, condition: Option<Condition>) -> bool {
+        self.breakpoints.insert(addr, condition).is_none()
+    }
+
+    /// returns false if there was no breakpoint at addr
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    pub fn has_breakpoint(&self, addr: usize) -> bool {
+        self.breakpoints.contains_key(&addr)
+    }
+
+    /// names addresses from here on - see Symbols
+    pub fn load_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// addr in hex, followed by its symbol if there is one, Ex. `0x02A6 <main_loop+0x4>`
+    pub fn locate(&self, addr: usize) -> String {
+        match self.symbols.name(addr) {
+            Some(name) => format!("{:#06X} <{}>", addr, name),
+            None => format!("{:#06X}", addr),
+        }
+    }
+
+    /// stop, with the breakpoint named by its symbol
+    pub fn describe(&self, stop: &Stop) -> String {
+        match stop {
+            Stop::Breakpoint(addr) => format!("Breakpoint at {}", self.locate(*addr)),
+            _ => stop.to_string(),
+        }
+    }
+
+    /// PC, then the return address of each call on the stack, innermost first
+    pub fn backtrace(&self, chip8: &Chip8) -> String {
+        std::iter::once(chip8.ram.PC)
+            .chain(chip8.ram.stack.iter().rev().copied())
+            .enumerate()
+            .map(|(frame, addr)| format!("#{:<2} {}", frame, self.locate(addr)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// stops when target changes, as long as condition (if any) holds then.
+    /// Returns false if target was already watched - its condition is replaced.
+    pub fn add_watchpoint(
+        &mut self,
+        target: Target,
+        condition: Option<Condition>,
+        chip8: &Chip8,
+    ) -> bool {
+        if let Some(watchpoint) = self
+            .watchpoints
+            .iter_mut()
+            .find(|watchpoint| watchpoint.target == target)
+        {
+            watchpoint.condition = condition;
+            return false;
+        }
+
+        self.watchpoints.push(Watchpoint {
+            target,
+            value: target.read(chip8),
+            condition,
+        });
+        true
+    }
+
+    /// returns false if target wasn't watched
+    pub fn remove_watchpoint(&mut self, target: Target) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.target != target);
+        self.watchpoints.len() != len
+    }
+
+    /// logs every instruction executed from here on - see Trace
+    pub fn start_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// returns false if there was no trace running
+    pub fn stop_trace(&mut self) -> bool {
+        self.trace.take().is_some()
+    }
+
+    /// counts every instruction executed from here on - see Profiler
+    pub fn start_profile(&mut self) {
+        self.profiler = Some(Profiler::default());
+    }
+
+    /// the profile so far, if there is one running. Profiling stops.
+    pub fn stop_profile(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// remembers memory as it is now, so examine can highlight what changes
+    pub fn mark_memory(&mut self, chip8: &Chip8) {
+        self.marked.clone_from(&chip8.ram.mem);
+    }
+
+    /// memory viewer - bytes changed since execution last resumed are highlighted
+    pub fn examine(&self, chip8: &Chip8, addr: usize, len: usize, layout: Layout) -> String {
+        memory::view(&chip8.ram.mem, &self.marked, addr, len, layout)
+    }
+
+    /// executes up to count instructions, stopping early at a breakpoint,
+    /// watchpoint or error
+    pub fn step(&mut self, chip8: &mut Chip8, count: usize) -> Result<Option<Stop>, Chip8Error> {
+        self.mark_memory(chip8);
+
+        for _ in 0..count {
+            if chip8.is_halted() {
+                break;
+            }
+
+            if let Some(stop) = self.step_once(chip8)? {
+                return Ok(Some(stop));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// executes up to the end of the current frame, stopping early at a
+    /// breakpoint, watchpoint or error
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        // nothing to check - let the Chip8 run the frame itself
+        if self.breakpoints.is_empty()
+            && self.watchpoints.is_empty()
+            && self.trace.is_none()
+            && self.profiler.is_none()
+        {
+            return chip8.run_frame().map(|_| None);
+        }
+
+        let frame = chip8.scheduler.frame();
+        while chip8.scheduler.frame() == frame && !chip8.is_halted() {
+            if let Some(stop) = self.step_once(chip8)? {
+                return Ok(Some(stop));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn step_once(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        if let Some(trace) = self.trace.as_mut() {
+            if !chip8.is_waiting_for_key() {
+                if let Err(err) = trace.record(chip8, &self.symbols) {
+                    self.trace = None;
+                    return Ok(Some(Stop::TraceFailed(err.to_string())));
+                }
+            }
+        }
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            if !chip8.is_waiting_for_key() {
+                profiler.record(chip8);
+            }
+        }
+
+        // FX0A waiting on a key - nothing ran, so nothing can have changed
+        if chip8.is_waiting_for_key() {
+            chip8.step()?;
+            return Ok(None);
+        }
+        chip8.step()?;
+
+        let holds =
+            |condition: &Option<Condition>| condition.as_ref().is_none_or(|c| c.eval(chip8));
+
+        for watchpoint in self.watchpoints.iter_mut() {
+            let new = watchpoint.target.read(chip8);
+            if new != watchpoint.value {
+                let old = std::mem::replace(&mut watchpoint.value, new);
+                if holds(&watchpoint.condition) {
+                    return Ok(Some(Stop::Watchpoint {
+                        target: watchpoint.target,
+                        old,
+                        new,
+                    }));
+                }
+            }
+        }
+
+        if self.breakpoints.get(&chip8.ram.PC).is_some_and(holds) {
+            return Ok(Some(Stop::Breakpoint(chip8.ram.PC)));
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // 7001 7101 1200 - ADD V0, 1; ADD V1, 1; JP 0x200
+    const PROGRAM: &[u8] = &[0x70, 0x01, 0x71, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn waits_for_keys_without_stopping() {
+        // F30A 7001 - LD V3, K; ADD V0, 1
+        let mut chip8 = Chip8::new(&[0xF3, 0x0A, 0x70, 0x01], Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x202, None);
+
+        // arriving on the breakpoint stops once, even though FX0A is waiting
+        assert_eq!(
+            debugger.step(&mut chip8, 10),
+            Ok(Some(Stop::Breakpoint(0x202)))
+        );
+        assert!(chip8.is_waiting_for_key());
+        for _ in 0..5 {
+            assert_eq!(debugger.step(&mut chip8, 10), Ok(None));
+        }
+
+        chip8.set_key(0x9, true);
+        chip8.set_key(0x9, false);
+        assert_eq!(debugger.step(&mut chip8, 1), Ok(None));
+        assert_eq!((chip8.ram.V[3], chip8.ram.V[0]), (0x9, 1));
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x204, None);
+
+        assert_eq!(
+            debugger.step(&mut chip8, 10),
+            Ok(Some(Stop::Breakpoint(0x204)))
+        );
+        assert_eq!(chip8.ram.V[0..2], [1, 1]);
+
+        // resuming runs the breakpoint's instruction before stopping again
+        assert_eq!(
+            debugger.step(&mut chip8, 10),
+            Ok(Some(Stop::Breakpoint(0x204)))
+        );
+        assert_eq!(chip8.ram.V[0..2], [2, 2]);
+
+        debugger.remove_breakpoint(0x204);
+        assert_eq!(debugger.run_frame(&mut chip8), Ok(None));
+    }
+
+    #[test]
+    fn names_stops_and_frames_with_symbols() {
+        // 2204 1200 00EE - CALL 0x204; JP 0x200; RET
+        let mut chip8 =
+            Chip8::new(&[0x22, 0x04, 0x12, 0x00, 0x00, 0xEE], Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.load_symbols("main = 0x200\nreturn = 0x204".parse().unwrap());
+        debugger.add_breakpoint(0x204, None);
+
+        let stop = debugger.step(&mut chip8, 1).unwrap().unwrap();
+        assert_eq!(debugger.describe(&stop), "Breakpoint at 0x0204 <return>");
+        assert_eq!(
+            debugger.backtrace(&chip8),
+            "#0  0x0204 <return>\n#1  0x0202 <main+0x2>"
+        );
+    }
+
+    #[test]
+    fn stops_at_watchpoints() {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_watchpoint(Target::V(1), None, &chip8);
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8),
+            Ok(Some(Stop::Watchpoint {
+                target: Target::V(1),
+                old: 0,
+                new: 1
+            }))
+        );
+        assert_eq!(chip8.ram.PC, 0x204);
+    }
+
+    #[test]
+    fn stops_only_when_conditions_hold() {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x204, Some("V0 == 3".parse().unwrap()));
+        debugger.add_watchpoint(Target::V(1), Some("V1 > 4".parse().unwrap()), &chip8);
+
+        // the breakpoint is passed twice before V0 gets to 3
+        assert_eq!(
+            debugger.step(&mut chip8, 100),
+            Ok(Some(Stop::Breakpoint(0x204)))
+        );
+        assert_eq!(chip8.ram.V[0..2], [3, 3]);
+
+        debugger.remove_breakpoint(0x204);
+        assert_eq!(
+            debugger.step(&mut chip8, 100),
+            Ok(Some(Stop::Watchpoint {
+                target: Target::V(1),
+                old: 4,
+                new: 5
+            }))
+        );
+    }
+
+    #[test]
+    fn traces_executed_instructions() {
+        let path = std::env::temp_dir().join(format!("chippy-trace-{}.log", std::process::id()));
+        // F00A 7001 - LD V0, K; ADD V0, 1
+        let mut chip8 = Chip8::new(&[0xF0, 0x0A, 0x70, 0x01], Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.start_trace(Trace::create(&path).unwrap());
+
+        // the cycles spent waiting on a key aren't instructions
+        debugger.step(&mut chip8, 3).unwrap();
+        chip8.set_key(0x4, true);
+        chip8.set_key(0x4, false);
+        debugger.step(&mut chip8, 1).unwrap();
+        debugger.stop_trace();
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = trace.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("CYC:0 PC:0200 OP:F00A V0:00"));
+        assert!(lines[1].starts_with("CYC:1 PC:0202 OP:7001 V0:04"));
+    }
+
+    #[test]
+    fn marks_memory_when_stepping() {
+        // A300 F033 - LD I, 0x300; LD B, V0
+        let mut chip8 = Chip8::new(&[0xA3, 0x00, 0xF0, 0x33], Quirks::default()).unwrap();
+        chip8.ram.V[0] = 123;
+        let mut debugger = Debugger::default();
+
+        debugger.step(&mut chip8, 2).unwrap();
+        assert_eq!(debugger.marked[0x300..0x303], [0, 0, 0]);
+        assert_eq!(chip8.ram.mem[0x300..0x303], [1, 2, 3]);
+
+        debugger.step(&mut chip8, 0).unwrap();
+        assert_eq!(debugger.marked[0x300..0x303], [1, 2, 3]);
+    }
+}
@@ -0,0 +1,260 @@
+use super::Debugger;
+use crate::chip8::Chip8;
+use crate::disassembler;
+
+/// panel size in characters
+pub const COLUMNS: usize = 26;
+pub const ROWS: usize = 20;
+
+/// panel size in pixels - characters are 5x7 in a 6x8 cell
+pub const WIDTH: u32 = COLUMNS as u32 * 6;
+pub const HEIGHT: u32 = ROWS as u32 * 8;
+
+const BACKGROUND: [u8; 4] = [20, 20, 20, 0];
+const TEXT: [u8; 4] = [200, 200, 200, 0];
+const HIGHLIGHT: [u8; 4] = [5, 110, 5, 0];
+
+/// instructions listed from PC onwards
+const DISASSEMBLY_LINES: usize = 5;
+
+/// hex keys as they're laid out on the COSMAC VIP keypad
+const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// a character and whether it's highlighted
+type Cell = (char, bool);
+
+/// Renders the debug panel shown next to the game - registers, timers, the
+/// stack, a disassembly from PC and the keypad - as WIDTH x HEIGHT
+/// [r, g, b, 0] pixels, the same format as Display::get_raw_bytes.
+pub fn render(chip8: &Chip8, debugger: &Debugger) -> Vec<u8> {
+    let lines = lines(chip8, debugger);
+    let mut pixels = Vec::with_capacity((WIDTH * HEIGHT) as usize * 4);
+
+    for y in 0..HEIGHT as usize {
+        let line = lines.get(y / 8).map_or(&[][..], |line| line.as_slice());
+        for x in 0..WIDTH as usize {
+            let (ch, highlighted) = line.get(x / 6).copied().unwrap_or((' ', false));
+            let lit = glyph_pixel(ch, x % 6, y % 8);
+            let color = match (lit, highlighted) {
+                (true, false) => TEXT,
+                (true, true) => BACKGROUND,
+                (false, true) => HIGHLIGHT,
+                (false, false) => BACKGROUND,
+            };
+            pixels.extend_from_slice(&color);
+        }
+    }
+
+    pixels
+}
+
+fn lines(chip8: &Chip8, debugger: &Debugger) -> Vec<Vec<Cell>> {
+    let ram = &chip8.ram;
+    let mut lines = vec![
+        plain(&format!(
+            "PC {:04X}  I {:04X}  SP {}",
+            ram.PC,
+            ram.I,
+            ram.stack.len()
+        )),
+        plain(&format!(
+            "DT {:02X}    ST {:02X}",
+            ram.delay_timer, ram.sound_timer
+        )),
+        vec![],
+    ];
+
+    lines.extend(ram.V.chunks(4).enumerate().map(|(row, registers)| {
+        let registers = registers
+            .iter()
+            .enumerate()
+            .map(|(col, value)| format!("V{:X} {:02X}", row * 4 + col, value))
+            .collect::<Vec<String>>();
+        plain(&registers.join(" "))
+    }));
+    lines.push(vec![]);
+
+    // the most recent return addresses - SP has the full depth
+    let stack = ram
+        .stack
+        .iter()
+        .rev()
+        .take(4)
+        .map(|addr| format!(" {:04X}", addr))
+        .collect::<String>();
+    lines.push(plain(&format!("STACK{}", stack)));
+    lines.push(vec![]);
+
+    let mut addr = ram.PC;
+    for _ in 0..DISASSEMBLY_LINES {
+        if addr >= ram.mem.len() {
+            break;
+        }
+
+        let (mnemonic, len) = disassembler::disassemble_with(&ram.mem[addr..], debugger.symbols());
+        let marker = if debugger.has_breakpoint(addr) {
+            '*'
+        } else {
+            ' '
+        };
+        let line = format!("{}{:04X} {}", marker, addr, mnemonic);
+        lines.push(if addr == ram.PC {
+            highlighted(&line)
+        } else {
+            plain(&line)
+        });
+        addr += len;
+    }
+    lines.resize(ROWS - KEYPAD.len() - 1, vec![]);
+    lines.push(vec![]);
+
+    for (row, keys) in KEYPAD.iter().enumerate() {
+        let mut line = plain(if row == 0 { "KEYS " } else { "     " });
+        for &key in keys {
+            line.push((
+                char::from_digit(key as u32, 16).map_or('?', |ch| ch.to_ascii_uppercase()),
+                chip8.is_pressed(key),
+            ));
+            line.push((' ', false));
+        }
+        lines.push(line);
+    }
+
+    for line in lines.iter_mut() {
+        line.truncate(COLUMNS);
+    }
+    lines
+}
+
+fn plain(text: &str) -> Vec<Cell> {
+    text.chars().map(|ch| (ch, false)).collect()
+}
+
+fn highlighted(text: &str) -> Vec<Cell> {
+    let mut cells = text.chars().map(|ch| (ch, true)).collect::<Vec<Cell>>();
+    // highlight the whole width so the line reads as a bar
+    cells.resize(COLUMNS, (' ', true));
+    cells
+}
+
+/// true if the pixel at x, y of ch's 6x8 cell is lit
+fn glyph_pixel(ch: char, x: usize, y: usize) -> bool {
+    if x >= 5 || y >= 7 {
+        return false;
+    }
+    glyph(ch)[y] >> (4 - x) & 1 == 1
+}
+
+/// 5x7 rows, bit 4 is the leftmost pixel. Lowercase letters other than
+/// the x in 0x share the uppercase glyphs.
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        _ if ch == 'x' => [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11],
+        ' ' => [0x00; 7],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    fn text(line: &[Cell]) -> String {
+        line.iter().map(|&(ch, _)| ch).collect()
+    }
+
+    #[test]
+    fn lists_machine_state() {
+        // LD VA, 0x2F; CALL 0x206; CLS
+        let program = [0x6A, 0x2F, 0x22, 0x06, 0x00, 0xE0, 0x00, 0xEE];
+        let mut chip8 = Chip8::new(&program, Quirks::default()).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x202, None);
+        chip8.step().unwrap();
+        chip8.set_key(0x5, true);
+
+        let panel = lines(&chip8, &debugger);
+        assert_eq!(panel.len(), ROWS);
+        assert_eq!(text(&panel[0]), "PC 0202  I 0000  SP 0");
+        assert_eq!(text(&panel[5]), "V8 00 V9 00 VA 2F VB 00");
+        assert_eq!(text(&panel[10]).trim_end(), "*0202 CALL 0x206");
+        assert!(panel[10].iter().all(|&(_, highlighted)| highlighted));
+        assert_eq!(text(&panel[11]), " 0204 CLS");
+        assert_eq!(text(&panel[17]), "     4 5 6 D ");
+        assert_eq!(panel[17][7], ('5', true));
+        assert_eq!(panel[17][5], ('4', false));
+
+        chip8.step().unwrap();
+        assert_eq!(text(&lines(&chip8, &debugger)[8]), "STACK 0204");
+    }
+
+    #[test]
+    fn renders_glyphs() {
+        let chip8 = Chip8::new(&[0x00, 0xE0], Quirks::default()).unwrap();
+        let pixels = render(&chip8, &Debugger::default());
+        assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+
+        // the top left of P is lit and its top right isn't
+        let pixel = |x: usize, y: usize| &pixels[(y * WIDTH as usize + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), TEXT);
+        assert_eq!(pixel(4, 0), BACKGROUND);
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::chip8::Chip8;
+use crate::instruction::Instruction;
+use crate::symbols::Symbols;
+use crate::Result;
+
+/// how many of the hottest addresses the text report lists
+const HOT_ADDRESSES: usize = 10;
+
+/// Counts executed instructions per address and per opcode class, and
+/// attributes them to subroutines.
+///
+/// Calls are followed through Ram.stack - a 2NNN pushes a frame for its
+/// target and a 00EE pops it. Each subroutine gets the instructions run
+/// while it's on the stack (cycles, once per instruction however deep it
+/// has recursed) and the ones run while it's on top of it (self_cycles).
+/// Calls already on the stack when profiling starts are named after where
+/// execution was at the time.
+#[derive(Default)]
+pub struct Profiler {
+    cycles: u64,
+    addresses: HashMap<usize, u64>,
+    classes: HashMap<&'static str, u64>,
+    subroutines: HashMap<usize, Subroutine>,
+    /// entry address of each call on Ram.stack
+    frames: Vec<usize>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Subroutine {
+    calls: u64,
+    cycles: u64,
+    self_cycles: u64,
+}
+
+impl Profiler {
+    /// counts the instruction chip8 is about to execute
+    pub fn record(&mut self, chip8: &Chip8) {
+        let ram = &chip8.ram;
+
+        self.frames.truncate(ram.stack.len());
+        while self.frames.len() < ram.stack.len() {
+            self.frames.push(ram.PC);
+            self.subroutines.entry(ram.PC).or_default().calls += 1;
+        }
+
+        *self.addresses.entry(ram.PC).or_default() += 1;
+        if let Some(&[hi_byte, lo_byte]) = ram.mem.get(ram.PC..ram.PC + 2) {
+            *self.classes.entry(class(hi_byte, lo_byte)).or_default() += 1;
+        }
+        for (depth, &entry) in self.frames.iter().enumerate() {
+            // a recursive subroutine's outer frames already counted it
+            if !self.frames[..depth].contains(&entry) {
+                self.subroutines.entry(entry).or_default().cycles += 1;
+            }
+        }
+        if let Some(&entry) = self.frames.last() {
+            self.subroutines.entry(entry).or_default().self_cycles += 1;
+        }
+
+        self.cycles += 1;
+    }
+
+    /// everything counted so far, busiest first, with addresses named by symbols
+    pub fn report(&self, symbols: &Symbols) -> Report {
+        let mut addresses = self
+            .addresses
+            .iter()
+            .map(|(&address, &count)| AddressCount {
+                address,
+                name: symbols.name(address),
+                count,
+            })
+            .collect::<Vec<AddressCount>>();
+        addresses.sort_by_key(|row| (u64::MAX - row.count, row.address));
+
+        let mut classes = self
+            .classes
+            .iter()
+            .map(|(&class, &count)| ClassCount {
+                class: class.to_string(),
+                count,
+            })
+            .collect::<Vec<ClassCount>>();
+        classes.sort_by(|a, b| b.count.cmp(&a.count).then(a.class.cmp(&b.class)));
+
+        let mut subroutines = self
+            .subroutines
+            .iter()
+            .map(|(&address, subroutine)| SubroutineCount {
+                address,
+                name: symbols.name(address),
+                calls: subroutine.calls,
+                cycles: subroutine.cycles,
+                self_cycles: subroutine.self_cycles,
+            })
+            .collect::<Vec<SubroutineCount>>();
+        subroutines.sort_by_key(|row| (u64::MAX - row.cycles, row.address));
+
+        Report {
+            cycles: self.cycles,
+            addresses,
+            classes,
+            subroutines,
+        }
+    }
+}
+
+/// A profile, printable as a summary or saved as CSV or JSON
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub cycles: u64,
+    pub addresses: Vec<AddressCount>,
+    pub classes: Vec<ClassCount>,
+    pub subroutines: Vec<SubroutineCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddressCount {
+    pub address: usize,
+    pub name: Option<String>,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassCount {
+    pub class: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubroutineCount {
+    pub address: usize,
+    pub name: Option<String>,
+    pub calls: u64,
+    pub cycles: u64,
+    pub self_cycles: u64,
+}
+
+impl Report {
+    /// writes the report as CSV or JSON, going by path's extension
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => self.to_csv(),
+            Some("json") => serde_json::to_string_pretty(self)?,
+            _ => return Err("profiles are saved as .csv or .json".into()),
+        };
+
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// one table for everything - kind is address, class or subroutine, and
+    /// columns that don't apply to a kind are left empty
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,address,name,count,cycles,self_cycles\n");
+        let name = |name: &Option<String>| csv_field(name.as_deref().unwrap_or_default());
+
+        for row in &self.addresses {
+            csv += &format!(
+                "address,{:#06X},{},{},,\n",
+                row.address,
+                name(&row.name),
+                row.count
+            );
+        }
+        for row in &self.classes {
+            csv += &format!("class,,{},{},,\n", row.class, row.count);
+        }
+        for row in &self.subroutines {
+            csv += &format!(
+                "subroutine,{:#06X},{},{},{},{}\n",
+                row.address,
+                name(&row.name),
+                row.calls,
+                row.cycles,
+                row.self_cycles
+            );
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |count: u64| count as f64 * 100.0 / self.cycles.max(1) as f64;
+        let location = |address: usize, name: &Option<String>| match name {
+            Some(name) => format!("{:#06X} <{}>", address, name),
+            None => format!("{:#06X}", address),
+        };
+
+        writeln!(f, "{} instructions executed", self.cycles)?;
+
+        writeln!(f, "\nHottest addresses")?;
+        for row in self.addresses.iter().take(HOT_ADDRESSES) {
+            let location = location(row.address, &row.name);
+            writeln!(
+                f,
+                "  {:<32} {:>10} {:>6.2}%",
+                location,
+                row.count,
+                percent(row.count)
+            )?;
+        }
+
+        writeln!(f, "\nOpcode classes")?;
+        for row in &self.classes {
+            writeln!(
+                f,
+                "  {:<32} {:>10} {:>6.2}%",
+                row.class,
+                row.count,
+                percent(row.count)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\n{:<34} {:>8} {:>10} {:>10}",
+            "Subroutines", "calls", "cycles", "self"
+        )?;
+        for row in &self.subroutines {
+            writeln!(
+                f,
+                "  {:<32} {:>8} {:>10} {:>10}",
+                location(row.address, &row.name),
+                row.calls,
+                row.cycles,
+                row.self_cycles
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// quotes field if it has a comma, quote or line break in it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// the opcode pattern an instruction matches, Ex. DXYN
+fn class(hi_byte: u8, lo_byte: u8) -> &'static str {
+    let Instruction {
+        first_nibble,
+        x,
+        y,
+        n,
+        nn,
+        ..
+    } = Instruction::from([hi_byte, lo_byte]);
+
+    match (first_nibble, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => "00E0",
+        (0x0, 0x0, 0xE, 0xE) => "00EE",
+        (0x0, 0x0, 0xC, _) => "00CN",
+        (0x0, 0x0, 0xD, _) => "00DN",
+        (0x0, 0x0, 0xF, 0xB) => "00FB",
+        (0x0, 0x0, 0xF, 0xC) => "00FC",
+        (0x0, 0x0, 0xF, 0xD) => "00FD",
+        (0x0, 0x0, 0xF, 0xE) => "00FE",
+        (0x0, 0x0, 0xF, 0xF) => "00FF",
+        (0x0, _, _, _) => "0NNN",
+        (0x1, _, _, _) => "1NNN",
+        (0x2, _, _, _) => "2NNN",
+        (0x3, _, _, _) => "3XNN",
+        (0x4, _, _, _) => "4XNN",
+        (0x5, _, _, 0x0) => "5XY0",
+        (0x5, _, _, 0x2) => "5XY2",
+        (0x5, _, _, 0x3) => "5XY3",
+        (0x6, _, _, _) => "6XNN",
+        (0x7, _, _, _) => "7XNN",
+        (0x8, _, _, 0x0) => "8XY0",
+        (0x8, _, _, 0x1) => "8XY1",
+        (0x8, _, _, 0x2) => "8XY2",
+        (0x8, _, _, 0x3) => "8XY3",
+        (0x8, _, _, 0x4) => "8XY4",
+        (0x8, _, _, 0x5) => "8XY5",
+        (0x8, _, _, 0x6) => "8XY6",
+        (0x8, _, _, 0x7) => "8XY7",
+        (0x8, _, _, 0xE) => "8XYE",
+        (0x9, _, _, 0x0) => "9XY0",
+        (0xA, _, _, _) => "ANNN",
+        (0xB, _, _, _) => "BNNN",
+        (0xC, _, _, _) => "CXNN",
+        (0xD, _, _, _) => "DXYN",
+        (0xE, _, _, _) if nn == 0x9E => "EX9E",
+        (0xE, _, _, _) if nn == 0xA1 => "EXA1",
+        (0xF, 0x0, _, _) if nn == 0x00 => "F000",
+        (0xF, _, _, _) => match nn {
+            0x01 => "FX01",
+            0x07 => "FX07",
+            0x0A => "FX0A",
+            0x15 => "FX15",
+            0x18 => "FX18",
+            0x1E => "FX1E",
+            0x29 => "FX29",
+            0x30 => "FX30",
+            0x33 => "FX33",
+            0x55 => "FX55",
+            0x65 => "FX65",
+            0x75 => "FX75",
+            0x85 => "FX85",
+            _ => "unknown",
+        },
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // 2206 1200 - CALL 0x206; JP 0x200
+    // 7001 00EE - ADD V0, 1; RET
+    const PROGRAM: &[u8] = &[0x22, 0x06, 0x12, 0x00, 0x00, 0x00, 0x70, 0x01, 0x00, 0xEE];
+
+    fn profile(steps: usize) -> Report {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        let mut profiler = Profiler::default();
+        for _ in 0..steps {
+            profiler.record(&chip8);
+            chip8.step().unwrap();
+        }
+        profiler.report(&"draw = 0x206".parse().unwrap())
+    }
+
+    #[test]
+    fn counts_addresses_and_classes() {
+        // CALL, ADD, RET, JP, then CALL and ADD again
+        let report = profile(6);
+
+        assert_eq!(report.cycles, 6);
+        assert_eq!(report.addresses[0].address, 0x200);
+        assert_eq!(report.addresses[0].count, 2);
+        assert_eq!(report.addresses[1].name.as_deref(), Some("draw"));
+
+        let classes = report
+            .classes
+            .iter()
+            .map(|row| (row.class.as_str(), row.count))
+            .collect::<Vec<(&str, u64)>>();
+        assert_eq!(
+            classes,
+            [("2NNN", 2), ("7XNN", 2), ("00EE", 1), ("1NNN", 1)]
+        );
+    }
+
+    #[test]
+    fn attributes_cycles_to_subroutines() {
+        let report = profile(6);
+        let draw = &report.subroutines[0];
+
+        assert_eq!(draw.address, 0x206);
+        assert_eq!(draw.calls, 2);
+        // ADD and RET from the first call, and the ADD of the one still running
+        assert_eq!(draw.cycles, 3);
+        assert_eq!(draw.self_cycles, 3);
+    }
+
+    #[test]
+    fn counts_recursion_once() {
+        // 2204 1202 - CALL 0x204; JP 0x202
+        // 7001 3003 2204 00EE - ADD V0, 1; SE V0, 3; CALL 0x204; RET
+        let program = [
+            0x22, 0x04, 0x12, 0x02, 0x70, 0x01, 0x30, 0x03, 0x22, 0x04, 0x00, 0xEE,
+        ];
+        let mut chip8 = Chip8::new(&program, Quirks::default()).unwrap();
+        let mut profiler = Profiler::default();
+        for _ in 0..13 {
+            profiler.record(&chip8);
+            chip8.step().unwrap();
+        }
+
+        let report = profiler.report(&Symbols::default());
+        let count = &report.subroutines[0];
+        assert_eq!(count.address, 0x204);
+        assert_eq!(count.calls, 3);
+        // everything but the first CALL and the JP, each counted once
+        assert_eq!(count.cycles, 11);
+        assert_eq!(count.self_cycles, 11);
+    }
+
+    #[test]
+    fn quotes_csv_names() {
+        assert_eq!(csv_field("draw"), "draw");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn exports_csv_and_json() {
+        let report = profile(6);
+
+        let csv = report.to_csv();
+        assert!(csv.starts_with("kind,address,name,count,cycles,self_cycles\n"));
+        assert!(csv.contains("\nclass,,2NNN,2,,\n"));
+        assert!(csv.contains("\nsubroutine,0x0206,draw,2,3,3\n"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["subroutines"][0]["name"], "draw");
+        assert_eq!(json["cycles"], 6);
+    }
+}
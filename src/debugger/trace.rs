@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::chip8::Chip8;
+use crate::symbols::Symbols;
+
+/// Writes a line per executed instruction with the machine state from just
+/// before it ran - space separated NAME:VALUE fields, hex unless noted,
+/// always in this order:
+///
+/// ```text
+/// CYC:0 PC:0200 OP:00E0 V0:00 V1:00 .. VF:00 I:0000 SP:0 DT:00 ST:00
+/// ```
+///
+/// - CYC - instructions executed since the machine started, not since the
+///   trace did, so a trace started mid-run lines up with a full log. Decimal.
+/// - PC, OP - address and first two bytes of the instruction
+/// - V0-VF, I - registers
+/// - SP - stack depth, decimal
+/// - DT, ST - delay and sound timers
+///
+/// Once symbols are loaded, lines end with a SYM field naming PC, Ex.
+/// `SYM:main_loop+0x4`. Lines for addresses before the first symbol don't
+/// have one.
+pub struct Trace {
+    out: BufWriter<File>,
+}
+
+impl Trace {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Trace {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// logs the instruction chip8 is about to execute
+    pub fn record(&mut self, chip8: &Chip8, symbols: &Symbols) -> io::Result<()> {
+        let mut line = line(chip8);
+        if let Some(name) = symbols.name(chip8.ram.PC) {
+            line += &format!(" SYM:{}", name);
+        }
+        writeln!(self.out, "{}", line)?;
+        Ok(())
+    }
+}
+
+fn line(chip8: &Chip8) -> String {
+    let ram = &chip8.ram;
+    let byte = |addr: usize| ram.mem.get(addr).copied().unwrap_or(0);
+
+    let registers = ram
+        .V
+        .iter()
+        .enumerate()
+        .map(|(x, value)| format!("V{:X}:{:02X}", x, value))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    format!(
+        "CYC:{} PC:{:04X} OP:{:02X}{:02X} {} I:{:04X} SP:{} DT:{:02X} ST:{:02X}",
+        chip8.executed(),
+        ram.PC,
+        byte(ram.PC),
+        byte(ram.PC + 1),
+        registers,
+        ram.I,
+        ram.stack.len(),
+        ram.delay_timer,
+        ram.sound_timer
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn formats_lines() {
+        // 6A2F 1200 - LD VA, 0x2F; JP 0x200
+        let mut chip8 = Chip8::new(&[0x6A, 0x2F, 0x12, 0x00], Quirks::default()).unwrap();
+        (0..2).for_each(|_| chip8.step().unwrap());
+        chip8.ram.V[0xF] = 1;
+        chip8.ram.I = 0x300;
+        chip8.ram.delay_timer = 0x3C;
+
+        // counted from boot, however late the trace starts
+        assert_eq!(
+            line(&chip8),
+            "CYC:2 PC:0200 OP:6A2F \
+             V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 \
+             V8:00 V9:00 VA:2F VB:00 VC:00 VD:00 VE:00 VF:01 \
+             I:0300 SP:0 DT:3C ST:00"
+        );
+    }
+}
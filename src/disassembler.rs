@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::instruction::Instruction;
+use crate::ram::Ram;
+use crate::symbols::Symbols;
+
+/// Decodes the instruction at the start of bytes into a Cowgod style
+/// mnemonic (Ex. `LD V1, 0x20`), with Octo's names for the SUPER-CHIP and
+/// XO-CHIP extensions. Returns the mnemonic and how many bytes it took -
+/// F000 NNNN is four bytes long. Anything that isn't an instruction comes
+/// back as `db` data.
+pub fn disassemble(bytes: &[u8]) -> (String, usize) {
+    decode(bytes, &|addr| format!("{:#05X}", addr))
+}
+
+/// disassemble, naming jump, call and index targets with symbols
+pub fn disassemble_with(bytes: &[u8], symbols: &Symbols) -> (String, usize) {
+    decode(bytes, &|addr| symbols.format(addr))
+}
+
+/// Lists a program as it's laid out from 0x200 - one instruction a line,
+/// commented with its address and bytes. Every jump and call target gets
+/// a label, sub_XXX for subroutines and label_XXX for everything else,
+/// unless symbols has a name for it. Every other symbol at the start of an
+/// instruction is listed as a label too.
+///
+/// The listing is valid assembler source, as long as the symbols are valid
+/// assembler names.
+pub fn listing(program: &[u8], symbols: &Symbols) -> String {
+    let mut lines = vec![];
+    let mut calls = BTreeSet::new();
+    let mut jumps = BTreeSet::new();
+    let mut offset = 0;
+
+    while offset < program.len() {
+        let bytes = &program[offset..];
+        let (_, len) = disassemble(bytes);
+
+        if len == 2 {
+            let instruction = Instruction::from([bytes[0], bytes[1]]);
+            match instruction.first_nibble {
+                0x2 => calls.insert(instruction.nnn as usize),
+                0x1 | 0xB => jumps.insert(instruction.nnn as usize),
+                _ => false,
+            };
+        }
+
+        lines.push((Ram::START_PRGM_REGISTER + offset, offset, len));
+        offset += len;
+    }
+
+    // only addresses an instruction starts at can be labeled
+    let labels = lines
+        .iter()
+        .filter_map(|&(addr, _, _)| {
+            if let Some(name) = symbols.get(addr) {
+                Some((addr, name.to_string()))
+            } else if calls.contains(&addr) {
+                Some((addr, format!("sub_{:03X}", addr)))
+            } else if jumps.contains(&addr) {
+                Some((addr, format!("label_{:03X}", addr)))
+            } else {
+                None
+            }
+        })
+        .collect::<BTreeMap<usize, String>>();
+
+    let address = |addr: usize| {
+        labels
+            .get(&addr)
+            .cloned()
+            .unwrap_or_else(|| format!("{:#05X}", addr))
+    };
+
+    let mut listing = String::new();
+    for (addr, offset, len) in lines {
+        if let Some(label) = labels.get(&addr) {
+            listing += &format!("{}:\n", label);
+        }
+
+        let bytes = &program[offset..offset + len];
+        let (mnemonic, _) = decode(bytes, &address);
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+
+        listing += &format!("    {:<24}; {:#05X}  {}\n", mnemonic, addr, hex);
+    }
+
+    listing
+}
+
+/// disassemble, with address formatting jump, call and index targets
+fn decode(bytes: &[u8], address: &dyn Fn(usize) -> String) -> (String, usize) {
+    let (hi_byte, lo_byte) = match *bytes {
+        [hi_byte, lo_byte, ..] => (hi_byte, lo_byte),
+        [byte] => return (format!("db {:#04X}", byte), 1),
+        [] => return (String::new(), 0),
+    };
+
+    let Instruction {
+        first_nibble,
+        x,
+        y,
+        n,
+        nn,
+        nnn,
+        ..
+    } = Instruction::from([hi_byte, lo_byte]);
+
+    let mnemonic = match (first_nibble, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+        (0x0, 0x0, 0xC, _) => format!("SCD {}", n),
+        (0x0, 0x0, 0xD, _) => format!("SCU {}", n),
+        (0x0, 0x0, 0xF, 0xB) => "SCR".to_string(),
+        (0x0, 0x0, 0xF, 0xC) => "SCL".to_string(),
+        (0x0, 0x0, 0xF, 0xD) => "EXIT".to_string(),
+        (0x0, 0x0, 0xF, 0xE) => "LOW".to_string(),
+        (0x0, 0x0, 0xF, 0xF) => "HIGH".to_string(),
+        (0x0, _, _, _) => format!("SYS {}", address(nnn as usize)),
+        (0x1, _, _, _) => format!("JP {}", address(nnn as usize)),
+        (0x2, _, _, _) => format!("CALL {}", address(nnn as usize)),
+        (0x3, _, _, _) => format!("SE V{:X}, {:#04X}", x, nn),
+        (0x4, _, _, _) => format!("SNE V{:X}, {:#04X}", x, nn),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x5, _, _, 0x2) => format!("SAVE V{:X} - V{:X}", x, y),
+        (0x5, _, _, 0x3) => format!("LOAD V{:X} - V{:X}", x, y),
+        (0x6, _, _, _) => format!("LD V{:X}, {:#04X}", x, nn),
+        (0x7, _, _, _) => format!("ADD V{:X}, {:#04X}", x, nn),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, {}", address(nnn as usize)),
+        (0xB, _, _, _) => format!("JP V0, {}", address(nnn as usize)),
+        (0xC, _, _, _) => format!("RND V{:X}, {:#04X}", x, nn),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF, 0x0, 0x0, 0x0) if bytes.len() >= 4 => {
+            let long_addr = u16::from_be_bytes([bytes[2], bytes[3]]);
+            return (format!("LD I, long {}", address(long_addr as usize)), 4);
+        }
+        (0xF, _, 0x0, 0x1) => format!("PLANE {}", x),
+        (0xF, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+        (0xF, _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF, _, 0x3, 0x0) => format!("LD HF, V{:X}", x),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF, _, 0x3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        (0xF, _, 0x7, 0x5) => format!("LD R, V{:X}", x),
+        (0xF, _, 0x8, 0x5) => format!("LD V{:X}, R", x),
+        _ => format!("db {:#04X}, {:#04X}", hi_byte, lo_byte),
+    };
+
+    (mnemonic, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassembles_instructions() {
+        assert_eq!(disassemble(&[0x61, 0x20]), ("LD V1, 0x20".to_string(), 2));
+        assert_eq!(disassemble(&[0xD0, 0x15]).0, "DRW V0, V1, 5");
+        assert_eq!(disassemble(&[0x22, 0xF0]).0, "CALL 0x2F0");
+        assert_eq!(disassemble(&[0x8A, 0xBE]).0, "SHL VA, VB");
+        assert_eq!(disassemble(&[0xF3, 0x65]).0, "LD V3, [I]");
+        assert_eq!(disassemble(&[0x00, 0xFF]).0, "HIGH");
+    }
+
+    #[test]
+    fn disassembles_long_index_load() {
+        let bytes = [0xF0, 0x00, 0x12, 0x34];
+        assert_eq!(disassemble(&bytes), ("LD I, long 0x1234".to_string(), 4));
+        // without the address there's nothing to load
+        assert_eq!(disassemble(&bytes[..2]).0, "db 0xF0, 0x00");
+    }
+
+    #[test]
+    fn falls_back_to_data() {
+        assert_eq!(disassemble(&[0x51, 0x21]).0, "db 0x51, 0x21");
+        assert_eq!(disassemble(&[0xAB]), ("db 0xAB".to_string(), 1));
+    }
+
+    #[test]
+    fn labels_jump_and_call_targets() {
+        // 2206 1200 00E0 00EE - CALL 0x206; JP 0x200; CLS; RET
+        let program = [0x22, 0x06, 0x12, 0x00, 0x00, 0xE0, 0x00, 0xEE];
+        let listing = listing(&program, &Symbols::default());
+        let lines = listing.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "label_200:");
+        assert!(lines[1].starts_with("    CALL sub_206 "));
+        assert!(lines[1].ends_with("; 0x200  2206"));
+        assert!(lines[2].starts_with("    JP label_200 "));
+        assert_eq!(lines[4], "sub_206:");
+        assert!(lines[5].starts_with("    RET "));
+    }
+
+    #[test]
+    fn names_labels_from_symbols() {
+        let program = [0x22, 0x06, 0x12, 0x00, 0x00, 0xE0, 0x00, 0xEE];
+        let symbols = "main = 0x200\nclear = 0x204\ndraw = 0x206"
+            .parse::<Symbols>()
+            .unwrap();
+        let listing = listing(&program, &symbols);
+        let lines = listing.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "main:");
+        assert!(lines[1].starts_with("    CALL draw "));
+        assert_eq!(lines[3], "clear:");
+        assert_eq!(disassemble_with(&[0x12, 0x08], &symbols).0, "JP draw+0x2");
+    }
+}
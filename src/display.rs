@@ -1,129 +1,193 @@
-use crate::sys_handles::video::Renderer;
-use sdl2::Sdl;
+use serde::{Deserialize, Serialize};
 
-use crate::Result;
+/// [r, g, b, 0] for each combination of the two bitplanes
+pub type Palette = [[u8; 4]; 4];
 
 pub struct DrawInfo<'a> {
     pub coords: (u8, u8),
     pub row_count: u8,
+    /// 8 for regular sprites, 16 for SUPER-CHIP 16x16 sprites
+    pub sprite_width: u8,
     pub sprites: &'a [u8],
+    /// wrap sprites around the screen edges instead of clipping them
+    pub wrap: bool,
 }
 
+/// The framebuffer. Knows nothing about windows - a frontend
+/// reads it back with `get_raw_bytes` and presents it however it likes.
+#[derive(Serialize, Deserialize)]
 pub struct Display {
-    /// 4 x 64 x 32 byte array
-    ///
-    /// 4 bytes represent a single rgb pixel [r, g, b, 0]
-    pub virtual_canvas: [[[u8; 4]; 64]; 32],
-    /// Wrapper around a Canvas that implements Render trait
-    pub renderer: Renderer,
+    /// height x width pixels - one bit per XO-CHIP bitplane, 0 is off
+    pub virtual_canvas: Vec<Vec<u8>>,
+    /// 128 x 64 SUPER-CHIP mode instead of 64 x 32
+    pub hires: bool,
+    /// bitmask of the planes drawing, clearing and scrolling apply to
+    pub planes: u8,
 }
 
-impl From<&Sdl> for Display {
-    fn from(sdl_ctx: &Sdl) -> Self {
-        let renderer = Renderer::from(sdl_ctx);
+impl Default for Display {
+    fn default() -> Self {
+        let (width, height) = Self::LORES;
         Display {
-            renderer,
-            virtual_canvas: [[[0; 4]; 64]; 32],
+            virtual_canvas: vec![vec![0; width]; height],
+            hires: false,
+            planes: 1,
         }
     }
 }
 
 impl Display {
-    /// Clears the canvas and the virtual byte canvas
-    pub fn clear(&mut self) -> Result<()> {
-        self.virtual_canvas = [[[0; 4]; 64]; 32];
-        self.renderer.clear()
+    pub const LORES: (usize, usize) = (64, 32);
+    pub const HIRES: (usize, usize) = (128, 64);
+
+    pub const PALETTE: Palette = [
+        [0, 0, 0, 0],
+        [5, 110, 5, 0],
+        [200, 170, 40, 0],
+        [230, 230, 230, 0],
+    ];
+
+    pub fn width(&self) -> usize {
+        self.virtual_canvas[0].len()
     }
 
-    /// Returns virtual_canvas as a flat byte Vec
-    pub fn get_raw_bytes(&mut self) -> Vec<u8> {
+    pub fn height(&self) -> usize {
+        self.virtual_canvas.len()
+    }
+
+    /// Clears the selected planes of the virtual byte canvas
+    pub fn clear(&mut self) {
+        let planes = self.planes;
+        self.virtual_canvas
+            .iter_mut()
+            .flatten()
+            .for_each(|pixel| *pixel &= !planes);
+    }
+
+    /// Switches between 64 x 32 and 128 x 64. Clears the canvas.
+    pub fn set_hires(&mut self, hires: bool) {
+        let (width, height) = if hires { Self::HIRES } else { Self::LORES };
+        self.hires = hires;
+        self.virtual_canvas = vec![vec![0; width]; height];
+    }
+
+    /// Scrolls the canvas up by row_count rows
+    pub fn scroll_up(&mut self, row_count: usize) {
+        self.scroll(0, -(row_count as isize));
+    }
+
+    /// Scrolls the canvas down by row_count rows
+    pub fn scroll_down(&mut self, row_count: usize) {
+        self.scroll(0, row_count as isize);
+    }
+
+    /// Scrolls the canvas left by column_count columns
+    pub fn scroll_left(&mut self, column_count: usize) {
+        self.scroll(-(column_count as isize), 0);
+    }
+
+    /// Scrolls the canvas right by column_count columns
+    pub fn scroll_right(&mut self, column_count: usize) {
+        self.scroll(column_count as isize, 0);
+    }
+
+    /// Shifts the selected planes by (dx, dy). Pixels shifted in are off.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let previous = self.virtual_canvas.clone();
+
+        for (row_idx, row) in self.virtual_canvas.iter_mut().enumerate() {
+            for (column_idx, pixel) in row.iter_mut().enumerate() {
+                let (src_row, src_column) = (row_idx as isize - dy, column_idx as isize - dx);
+                let shifted = if (0..height).contains(&src_row) && (0..width).contains(&src_column)
+                {
+                    previous[src_row as usize][src_column as usize]
+                } else {
+                    0
+                };
+
+                *pixel = (*pixel & !planes) | (shifted & planes);
+            }
+        }
+    }
+
+    /// Returns virtual_canvas as a flat [r, g, b, 0] byte Vec, colored with palette
+    pub fn get_raw_bytes(&self, palette: &Palette) -> Vec<u8> {
         self.virtual_canvas
             .iter()
-            .flat_map(|row| row.iter().flatten())
-            .copied()
+            .flat_map(|row| row.iter().flat_map(|&pixel| palette[pixel as usize]))
             .collect::<Vec<u8>>()
     }
 
-    /// Updates virtual_canvas and renders to window canvas
+    /// Updates virtual_canvas
     pub fn draw<F: FnMut(bool)>(
         &mut self,
         DrawInfo {
             coords,
             row_count,
+            sprite_width,
             sprites,
+            wrap,
         }: DrawInfo,
         mut flipped_bits_callback: F,
-    ) -> Result<()> {
-        let (x_coord, y_coord) = (coords.0 & 63, coords.1 & 31);
-
-        let final_row = if y_coord + row_count >= 32 {
-            32
-        } else {
-            y_coord + row_count
-        };
-
-        let final_column = if x_coord + 8 >= 64 { 64 } else { x_coord + 8 };
-
-        let rows = (y_coord..final_row).into_iter().enumerate();
+    ) {
+        let (width, height) = (self.width(), self.height());
+        let (x_coord, y_coord) = (coords.0 as usize % width, coords.1 as usize % height);
+        let bytes_per_row = sprite_width as usize / 8;
+        let plane_len = row_count as usize * bytes_per_row;
         let mut flipped = false;
 
-        for (sprite_idx, row_idx) in rows {
-            let mut row = self.virtual_canvas[row_idx as usize];
-            let sprite_pixel = sprites[sprite_idx];
-
-            for (idx, column_idx) in (x_coord..final_column).into_iter().enumerate() {
-                let pixel = row[column_idx as usize];
-                let bit_shift = 7 - idx;
-                // check bit
-                let is_on = (sprite_pixel >> bit_shift) & 1 == 1;
-
-                if is_on {
-                    if pixel == [5, 110, 5, 0] {
-                        row[column_idx as usize] = [0, 0, 0, 0];
-                        flipped = true;
-                    } else {
-                        row[column_idx as usize] = [5, 110, 5, 0];
+        // each selected plane consumes the next plane_len bytes of sprite data
+        let selected_planes = (0..8)
+            .map(|bit| 1u8 << bit)
+            .filter(|bit| self.planes & bit != 0);
+
+        for (plane, plane_sprites) in selected_planes.zip(sprites.chunks(plane_len)) {
+            for (sprite_idx, sprite_row) in plane_sprites.chunks(bytes_per_row).enumerate() {
+                let row_idx = match y_coord + sprite_idx {
+                    row_idx if row_idx < height => row_idx,
+                    row_idx if wrap => row_idx % height,
+                    _ => break,
+                };
+
+                let sprite_pixels = sprite_row
+                    .iter()
+                    .fold(0u16, |acc, &byte| acc << 8 | byte as u16);
+
+                for idx in 0..sprite_width as usize {
+                    let column_idx = match x_coord + idx {
+                        column_idx if column_idx < width => column_idx,
+                        column_idx if wrap => column_idx % width,
+                        _ => break,
+                    };
+
+                    let pixel = &mut self.virtual_canvas[row_idx][column_idx];
+                    let bit_shift = sprite_width as usize - 1 - idx;
+                    // check bit
+                    let is_on = (sprite_pixels >> bit_shift) & 1 == 1;
+
+                    if is_on {
+                        if *pixel & plane != 0 {
+                            flipped = true;
+                        }
+                        *pixel ^= plane;
                     }
                 }
             }
-
-            self.virtual_canvas[row_idx as usize] = row;
         }
 
-        let bytes = self.get_raw_bytes();
-        self.renderer.render(&bytes)?;
         flipped_bits_callback(flipped);
-        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sys_handles::video::Renderable;
-
-    pub struct MockCanvas {}
-
-    impl Renderable for MockCanvas {
-        fn render(&mut self, _bytes: &[u8]) -> Result<()> {
-            Ok(())
-        }
-
-        fn clear(&mut self) -> Result<()> {
-            Ok(())
-        }
-    }
 
     #[allow(dead_code)]
     fn setup() -> Display {
-        let renderer = Renderer {
-            canvas: Box::new(MockCanvas {}),
-        };
-
-        Display {
-            renderer,
-            virtual_canvas: [[[0; 4]; 64]; 32],
-        }
+        Display::default()
     }
 
     #[test]
@@ -132,13 +196,15 @@ mod tests {
         let draw_info = DrawInfo {
             coords: (10, 20),
             row_count: 5,
+            sprite_width: 8,
             sprites: &[0xF0, 0x90, 0xF0, 0x90, 0x90], // letter "A",
+            wrap: false,
         };
         let mock_cb = |_bool_arg| {};
 
-        let _ = display.draw(draw_info, mock_cb);
+        display.draw(draw_info, mock_cb);
 
-        assert!(display.virtual_canvas[20][10] == [5, 110, 5, 0])
+        assert!(display.virtual_canvas[20][10] == 1)
     }
 
     #[test]
@@ -148,13 +214,15 @@ mod tests {
         let draw_info = DrawInfo {
             coords: (10, 20),
             row_count: 5,
+            sprite_width: 8,
             sprites: &[0xF0, 0x90, 0xF0, 0x90, 0x90],
+            wrap: false,
         };
 
-        let _ret1 = display.draw(draw_info, mock_cb);
-        let _ret2 = display.clear();
+        display.draw(draw_info, mock_cb);
+        display.clear();
 
-        assert!(display.virtual_canvas[20][10] == [0, 0, 0, 0])
+        assert!(display.virtual_canvas[20][10] == 0)
     }
 
     #[test]
@@ -164,10 +232,12 @@ mod tests {
         let draw_info = DrawInfo {
             coords: (60, 20),
             row_count: 5,
+            sprite_width: 8,
             sprites: &[0xF0, 0x90, 0xF0, 0x90, 0x90],
+            wrap: false,
         };
 
-        let _ret1 = display.draw(draw_info, mock_cb);
+        display.draw(draw_info, mock_cb);
 
         assert!(true == true);
     }
@@ -179,11 +249,119 @@ mod tests {
         let draw_info = DrawInfo {
             coords: (60, 30),
             row_count: 5,
+            sprite_width: 8,
             sprites: &[0xF0, 0x90, 0xF0, 0x90, 0x90],
+            wrap: false,
         };
 
-        let _ret1 = display.draw(draw_info, mock_cb);
+        display.draw(draw_info, mock_cb);
 
         assert!(true == true);
     }
+
+    #[test]
+    fn clips_sprites() {
+        let mut display = setup();
+        let draw_info = DrawInfo {
+            coords: (60, 30),
+            row_count: 5,
+            sprite_width: 8,
+            sprites: &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            wrap: false,
+        };
+
+        display.draw(draw_info, |_| {});
+
+        assert!(display.virtual_canvas[31][63] == 1);
+        assert!(display.virtual_canvas[0][0] == 0);
+    }
+
+    #[test]
+    fn wraps_sprites() {
+        let mut display = setup();
+        let draw_info = DrawInfo {
+            coords: (60, 30),
+            row_count: 5,
+            sprite_width: 8,
+            sprites: &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            wrap: true,
+        };
+
+        display.draw(draw_info, |_| {});
+
+        assert!(display.virtual_canvas[31][63] == 1);
+        assert!(display.virtual_canvas[0][0] == 1);
+        assert!(display.virtual_canvas[2][3] == 1);
+    }
+
+    #[test]
+    fn draws_wide_sprites_in_hires() {
+        let mut display = setup();
+        display.set_hires(true);
+        let sprites = [0xFF; 32];
+        let draw_info = DrawInfo {
+            coords: (100, 40),
+            row_count: 16,
+            sprite_width: 16,
+            sprites: &sprites,
+            wrap: false,
+        };
+
+        display.draw(draw_info, |_| {});
+
+        assert_eq!(display.width(), 128);
+        assert!(display.virtual_canvas[55][115] == 1);
+        assert!(display.virtual_canvas[56][116] == 0);
+    }
+
+    #[test]
+    fn scrolls_virtual_canvas() {
+        let mut display = setup();
+        display.virtual_canvas[0][10] = 1;
+
+        display.scroll_down(4);
+        assert!(display.virtual_canvas[4][10] == 1);
+        assert!(display.virtual_canvas[0][10] == 0);
+
+        display.scroll_right(4);
+        assert!(display.virtual_canvas[4][14] == 1);
+
+        display.scroll_left(8);
+        assert!(display.virtual_canvas[4][6] == 1);
+        assert!(display.virtual_canvas[4][63] == 0);
+    }
+
+    #[test]
+    fn draws_to_selected_planes() {
+        let mut display = setup();
+        display.planes = 0b11;
+        let draw_info = DrawInfo {
+            coords: (0, 0),
+            row_count: 1,
+            sprite_width: 8,
+            sprites: &[0x80, 0xC0],
+            wrap: false,
+        };
+
+        display.draw(draw_info, |_| {});
+
+        assert!(display.virtual_canvas[0][0] == 0b11);
+        assert!(display.virtual_canvas[0][1] == 0b10);
+
+        display.planes = 0b10;
+        display.clear();
+        assert!(display.virtual_canvas[0][0] == 0b01);
+        assert!(display.virtual_canvas[0][1] == 0);
+    }
+
+    #[test]
+    fn scrolls_selected_planes() {
+        let mut display = setup();
+        display.virtual_canvas[4][10] = 0b11;
+        display.planes = 0b10;
+
+        display.scroll_up(4);
+        assert!(display.virtual_canvas[0][10] == 0b10);
+        assert!(display.virtual_canvas[4][10] == 0b01);
+    }
 }
@@ -0,0 +1,291 @@
+use reqwest::{
+    blocking::Client,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    Url,
+};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cli::game;
+use crate::library::Library;
+use crate::ram::Ram;
+use crate::Result;
+
+/// Fetches ROMs over http(s), checking them before they're used and
+/// keeping them in the library so each url is only downloaded once.
+pub struct Downloader {
+    client: Client,
+    /// downloads are saved under dir/<host>/<url hash>/<file name>
+    dir: PathBuf,
+}
+
+/// a ROM from Downloader::fetch
+pub struct Download {
+    pub program: Vec<u8>,
+    /// where it's cached
+    pub path: PathBuf,
+    /// true if it came from the cache rather than the network
+    pub cached: bool,
+}
+
+impl Downloader {
+    /// the largest ROM that fits in memory
+    pub const MAX_SIZE: usize = Ram::MAX_PROGRAM_SIZE;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Self::TIMEOUT)
+            .connect_timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Downloader { client, dir })
+    }
+
+    /// saves into the `downloads` folder of the library's roms
+    pub fn for_library() -> Result<Self> {
+        Self::new(Library::default_dir().join("roms").join("downloads"))
+    }
+
+    /// the ROM at url, from the cache if it's been downloaded before. With
+    /// expected_sha1 the ROM must hash to it, and a cached copy that doesn't
+    /// is downloaded again.
+    pub fn fetch(&self, url: &str, expected_sha1: Option<&str>) -> Result<Download> {
+        let path = self.cache_path(url)?;
+        let matches = |program: &[u8]| {
+            expected_sha1.is_none_or(|expected| expected.eq_ignore_ascii_case(&game::hash(program)))
+        };
+
+        if let Ok(program) = fs::read(&path) {
+            if matches(&program) {
+                return Ok(Download {
+                    program,
+                    path,
+                    cached: true,
+                });
+            }
+        }
+
+        let program = self.download(url)?;
+        if !matches(&program) {
+            return Err(format!(
+                "{} has SHA-1 {}, expected {}",
+                url,
+                game::hash(&program),
+                expected_sha1.unwrap_or_default()
+            )
+            .into());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &program)?;
+
+        Ok(Download {
+            program,
+            path,
+            cached: false,
+        })
+    }
+
+    /// dir/<host>/<url hash>/<file name>. The hash covers the whole url,
+    /// query included, so every url gets its own file, and the file name is
+    /// the url's last path segment (given a .ch8 extension if it doesn't
+    /// have a ROM's) so the library can still title it.
+    fn cache_path(&self, url: &str) -> Result<PathBuf> {
+        let url = Url::parse(url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("expected an http/s url, got {}", url).into());
+        }
+
+        let host = url.host_str().ok_or("the url has no host")?;
+        let mut name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("download")
+            .replace(['\\', ':'], "_");
+
+        let is_rom = Path::new(&name).extension().is_some_and(|ext| {
+            Library::EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+        if !is_rom {
+            name.push_str(".ch8");
+        }
+
+        let url_hash = game::hash(url.as_str().as_bytes());
+        Ok(self.dir.join(host).join(&url_hash[..12]).join(name))
+    }
+
+    /// the body of a 2xx response, if it could be a ROM
+    fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let resp = self.client.get(url).send()?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(format!("{} responded with {}", url, status).into());
+        }
+
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        if ["text/html", "application/json", "application/xml"]
+            .iter()
+            .any(|kind| content_type.starts_with(kind))
+        {
+            return Err(format!("{} is {}, not a ROM", url, content_type).into());
+        }
+
+        let too_large = || format!("{} is larger than the {} byte maximum", url, Self::MAX_SIZE);
+        let length = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+        if length.is_some_and(|length| length > Self::MAX_SIZE) {
+            return Err(too_large().into());
+        }
+
+        // the header can be missing or wrong, so stop reading past the limit too
+        let mut program = vec![];
+        resp.take(Self::MAX_SIZE as u64 + 1)
+            .read_to_end(&mut program)?;
+        if program.len() > Self::MAX_SIZE {
+            return Err(too_large().into());
+        }
+
+        check(url, &program)?;
+        Ok(program)
+    }
+}
+
+/// rejects bodies that can't be a ROM
+fn check(url: &str, program: &[u8]) -> Result<()> {
+    if program.is_empty() {
+        return Err(format!("{} is empty", url).into());
+    }
+
+    let start = String::from_utf8_lossy(&program[..program.len().min(16)]).to_lowercase();
+    if ["<!doctype", "<html", "<?xml"]
+        .iter()
+        .any(|markup| start.trim_start().starts_with(markup))
+    {
+        return Err(format!("{} is a web page, not a ROM", url).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::thread;
+    use tiny_http::{Header, Response, Server};
+
+    const IBM: &[u8] = include_bytes!("../games/Ibm.ch8");
+
+    /// serves a few canned responses, counting the requests
+    fn serve() -> (String, Arc<AtomicUsize>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let html = Header::from_bytes("Content-Type", "text/html").unwrap();
+                let _ = match request.url() {
+                    url if url.starts_with("/roms/ibm") => {
+                        request.respond(Response::from_data(IBM))
+                    }
+                    "/page.ch8" => request
+                        .respond(Response::from_string("<html>Not Found</html>").with_header(html)),
+                    "/sneaky.ch8" => {
+                        request.respond(Response::from_string("\n<!DOCTYPE html><html>"))
+                    }
+                    "/empty.ch8" => request.respond(Response::from_data(vec![])),
+                    "/huge.ch8" => {
+                        request.respond(Response::from_data(vec![0; Downloader::MAX_SIZE + 1]))
+                    }
+                    _ => request.respond(Response::from_string("gone").with_status_code(404)),
+                };
+            }
+        });
+
+        (base, requests)
+    }
+
+    fn downloader(name: &str) -> Downloader {
+        let dir = std::env::temp_dir().join(format!("chippy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Downloader::new(dir).unwrap()
+    }
+
+    #[test]
+    fn caches_downloads() {
+        let (base, requests) = serve();
+        let downloader = downloader("download-cache");
+        let url = format!("{}/roms/ibm.ch8", base);
+
+        let first = downloader.fetch(&url, None).unwrap();
+        assert_eq!(first.program, IBM);
+        assert!(!first.cached);
+        assert!(first.path.ends_with("ibm.ch8"));
+
+        let second = downloader.fetch(&url, Some(&game::hash(IBM))).unwrap();
+        assert!(second.cached);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // a wrong hash doesn't trust the cache, then fails the new download
+        assert!(downloader.fetch(&url, Some("00")).is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // other urls, even ones that differ only in their query or
+        // extension, aren't answered from the same file
+        let mut paths = vec![first.path];
+        for other in ["/roms/ibm", "/roms/ibm.ch8?id=1", "/roms/ibm.ch8?id=2"] {
+            let download = downloader
+                .fetch(&format!("{}{}", base, other), None)
+                .unwrap();
+            assert!(!download.cached, "{}", other);
+            // ROMs get an extension so the library picks them up
+            assert!(download.path.ends_with("ibm.ch8"), "{}", other);
+            assert!(!paths.contains(&download.path), "{}", other);
+            paths.push(download.path);
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+
+        fs::remove_dir_all(&downloader.dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_responses() {
+        let (base, _) = serve();
+        let downloader = downloader("download-reject");
+
+        for path in [
+            "/missing.ch8",
+            "/page.ch8",
+            "/sneaky.ch8",
+            "/empty.ch8",
+            "/huge.ch8",
+        ] {
+            let url = format!("{}{}", base, path);
+            assert!(downloader.fetch(&url, None).is_err(), "{}", path);
+        }
+        assert!(downloader.fetch("ftp://example.com/ibm.ch8", None).is_err());
+        // nothing bad was cached
+        assert!(!downloader.dir.exists());
+    }
+}
@@ -1,52 +1,150 @@
-use sdl2::{event, keyboard::Scancode, EventPump};
-
+use sdl2::{
+    event,
+    keyboard::{Mod, Scancode},
+    EventPump,
+};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::display::{Display, DrawInfo};
-use crate::instruction::Instruction;
-use crate::ram::{Ram, Timer};
-use crate::sys_handles::{keyboard::Keyboard, sound::SoundSystem};
-
-#[derive(PartialEq)]
+use crate::chip8::Chip8;
+use crate::debugger::{command::Command, gdb::GdbStub, overlay, trace::Trace, Debugger};
+use crate::disassembler;
+use crate::display::{Display, Palette};
+use crate::error::{Chip8Error, ErrorPolicy};
+use crate::quirks::Quirks;
+use crate::rewind::Rewind;
+use crate::save_state::SaveSlots;
+use crate::scheduler::Scheduler;
+use crate::symbols::Symbols;
+use crate::sys_handles::{
+    keyboard::Keyboard,
+    sound::{Beep, SoundSystem},
+    video::{Frame, Renderer},
+};
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GameMode {
     Debug,
     Standard,
 }
 
+/// How to run a game, whether picked from the menu or passed on the command line
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub game_mode: GameMode,
+    pub quirks: Quirks,
+    /// instructions per second
+    pub ips: u32,
+    pub error_policy: ErrorPolicy,
+    /// window pixels per display pixel
+    pub scale: u32,
+    pub palette: Palette,
+    /// hex keys moved off their default host keys, by SDL key name
+    pub keymap: BTreeMap<u8, String>,
+    pub beep: Beep,
+}
+
+impl Settings {
+    pub const DEFAULT_SCALE: u32 = 10;
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            game_mode: GameMode::Standard,
+            quirks: Quirks::default(),
+            ips: Scheduler::DEFAULT_IPS,
+            error_policy: ErrorPolicy::default(),
+            scale: Self::DEFAULT_SCALE,
+            palette: Display::PALETTE,
+            keymap: BTreeMap::new(),
+            beep: Beep::default(),
+        }
+    }
+}
+
+/// SDL frontend - owns the window, audio device and event pump and
+/// drives a headless Chip8 with them.
 pub struct Emulator {
     pub event_pump: EventPump,
-    display: Display,
+    renderer: Renderer,
     keyboard: Keyboard,
     sound_system: SoundSystem,
-    loaded_ram: Ram,
+    chip8: Chip8,
+    debugger: Debugger,
+    gdb: Option<GdbStub>,
+    /// the debug panel as last rendered, None while it's hidden
+    panel: Option<Vec<u8>>,
+    /// where the profile is saved, printed when None
+    profile_output: Option<String>,
+    save_slots: SaveSlots,
+    rewind: Rewind,
+    /// true while the rewind hotkey is held
+    rewinding: bool,
+    palette: Palette,
     game_mode: GameMode,
+    error_policy: ErrorPolicy,
     last_cycle: Option<Instant>,
 }
 
 impl Emulator {
-    const CYCLE_RATE: u128 = Duration::from_millis(5).as_millis();
-
-    pub fn boot(program: Vec<u8>, game_mode: GameMode) -> Self {
+    /// number row keys for save slots 0-9
+    const SLOT_KEYS: [Scancode; 10] = [
+        Scancode::Num0,
+        Scancode::Num1,
+        Scancode::Num2,
+        Scancode::Num3,
+        Scancode::Num4,
+        Scancode::Num5,
+        Scancode::Num6,
+        Scancode::Num7,
+        Scancode::Num8,
+        Scancode::Num9,
+    ];
+
+    pub fn boot(program: Vec<u8>, settings: Settings) -> Result<Self> {
         let sdl_ctx = sdl2::init().unwrap();
         let event_pump = sdl_ctx.event_pump().unwrap();
 
-        let display = Display::from(&sdl_ctx);
-        let kb = Keyboard::new();
-        let loaded_ram = Ram::load(program.as_slice());
-        let sound_system = SoundSystem::new(&sdl_ctx);
+        let renderer = Renderer::new(&sdl_ctx, settings.scale);
+        let kb = Keyboard::new(&settings.keymap)?;
+        let save_slots = SaveSlots::for_program(&program);
+        let mut chip8 = Chip8::new(program.as_slice(), settings.quirks)?;
+        chip8.scheduler.set_ips(settings.ips);
+        let sound_system = SoundSystem::new(&sdl_ctx, settings.beep);
 
-        Emulator {
-            display,
+        Ok(Emulator {
+            renderer,
             event_pump,
             keyboard: kb,
-            loaded_ram,
+            chip8,
+            debugger: Debugger::default(),
+            gdb: None,
+            panel: None,
+            profile_output: None,
+            save_slots,
+            rewind: Rewind::default(),
+            rewinding: false,
+            palette: settings.palette,
             sound_system,
             last_cycle: None,
-            game_mode,
-        }
+            game_mode: settings.game_mode,
+            error_policy: settings.error_policy,
+        })
     }
 
     pub fn start(&mut self) {
+        if self.chip8.is_halted() {
+            self.report_profile();
+            return;
+        }
+
         match self.game_mode {
             GameMode::Debug => self.start_debug(),
             GameMode::Standard => self.start_loop(),
@@ -55,313 +153,419 @@ impl Emulator {
 
     fn start_loop(&mut self) {
         loop {
-            if self.game_mode == GameMode::Debug {
+            if self.game_mode == GameMode::Debug || self.chip8.is_halted() {
                 break;
             }
 
-            for ev in self.event_pump.poll_iter() {
-                if let event::Event::KeyDown {
-                    scancode: Some(code),
-                    ..
-                } = ev
-                {
-                    match code {
-                        Scancode::Space => self.game_mode = GameMode::Debug,
-                        Scancode::Return => self.game_mode = GameMode::Standard,
-                        _ => self.keyboard.press_key(code),
+            let events = self.event_pump.poll_iter().collect::<Vec<event::Event>>();
+
+            for ev in events {
+                match ev {
+                    event::Event::Quit { .. } => self.chip8.halt(),
+                    event::Event::KeyDown {
+                        scancode: Some(code),
+                        keymod,
+                        ..
+                    } => {
+                        if self.handle_save_hotkey(code, keymod) {
+                            continue;
+                        }
+
+                        match code {
+                            Scancode::Space => self.game_mode = GameMode::Debug,
+                            Scancode::Return => self.game_mode = GameMode::Standard,
+                            Scancode::Backspace => self.rewinding = true,
+                            Scancode::Tab => self.toggle_panel(),
+                            _ => self.set_key(code, true),
+                        }
                     }
+                    event::Event::KeyUp {
+                        scancode: Some(code),
+                        ..
+                    } => match code {
+                        Scancode::Backspace => self.rewinding = false,
+                        _ => self.set_key(code, false),
+                    },
+                    _ => (),
                 }
             }
 
+            if self.poll_gdb() {
+                // the GDB client has the machine stopped
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
             self.cycle();
         }
 
         self.start();
     }
 
+    /// reads debugger commands from stdin until one resumes or stops the game
     fn start_debug(&mut self) {
-        self.process_instruction(GameMode::Debug);
+        if self.panel.is_none() {
+            self.toggle_panel();
+        }
+        self.sync_frontend();
+        self.print_location();
+        let mut last_command = None;
 
-        loop {
-            if let event::Event::KeyDown {
-                scancode: Some(code),
-                ..
-            } = self.event_pump.wait_event()
-            {
-                match code {
-                    Scancode::Space => (),
-                    Scancode::Return => {
-                        self.game_mode = GameMode::Standard;
-                    }
-                    _ => {
-                        self.keyboard.press_key(code);
-                        // println!("Hit space to execute the next instruction");
-                        // println!("Hit return/enter to start the game loop again");
-                    }
-                }
+        while self.game_mode == GameMode::Debug && !self.chip8.is_halted() {
+            print!("(chippy) ");
+            io::stdout().flush().ok();
 
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = io::stdin().read_line(&mut line) {
+                self.chip8.halt();
                 break;
             }
-        }
-        self.start();
-
-    }
 
-    fn get_nums(value: &u8) -> (u8, u8, u8) {
-        let hundreds_digit = value / 100;
-        let tens_digit = (value / 10) % 10;
-        let ones_digit = value % 10;
-
-        (hundreds_digit, tens_digit, ones_digit)
-    }
-
-    fn is_pressed(&self, key_code: u8) -> bool {
-        self.keyboard.is_pressed(key_code)
-    }
-
-    pub fn cycle(&mut self) {
-        let time_elapsed = if let Some(instant) = self.last_cycle {
-            instant.elapsed().as_millis()
-        } else {
-            Self::CYCLE_RATE
-        };
-
-        if time_elapsed >= Self::CYCLE_RATE {
-            self.last_cycle = Some(Instant::now());
-            self.process_instruction(GameMode::Standard);
-        }
-    }
-
-    pub fn process_instruction(&mut self, game_mode: GameMode) {
-        if self.loaded_ram.delay_timer > 0 {
-            self.loaded_ram.delay_timer -= 1;
-        }
+            // an empty line repeats the last command
+            let command = if line.trim().is_empty() {
+                match last_command.take() {
+                    Some(command) => command,
+                    None => continue,
+                }
+            } else {
+                match line.parse::<Command>() {
+                    Ok(command) => command,
+                    Err(err) => {
+                        println!("{}", err);
+                        continue;
+                    }
+                }
+            };
 
-        if self.loaded_ram.sound_timer > 0 {
-            self.sound_system.device.resume();
-            self.loaded_ram.sound_timer -= 1;
-        } else if self.sound_system.is_playing() {
-            self.sound_system.device.pause();
+            self.run_command(command.clone());
+            self.sync_frontend();
+            last_command = Some(command);
         }
 
-        let instruction_bytes = self.loaded_ram.get_next_instruction();
-
-        let parsed_instruction = Instruction::from(instruction_bytes);
-
-        let Instruction {
-            first_nibble,
-            x,
-            y,
-            n,
-            nn,
-            nnn,
-            ..
-        } = parsed_instruction;
-
-        if game_mode == GameMode::Debug {
-            println!("{}", parsed_instruction);
-            println!("{}", self.loaded_ram);
-        }
+        self.start();
+    }
 
-        match (first_nibble, x, y, n) {
-            (0x0, 0x0, 0xE, 0x0) => {
-                let _ = self.display.clear();
-            }
-            (0x0, 0x0, 0xE, 0xE) => {
-                if let Some(return_addr) = self.loaded_ram.remove_addr() {
-                    self.loaded_ram.PC = return_addr as usize;
+    /// runs a debugger command, Ex. to set up breakpoints before the game starts
+    pub fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Break(addr, condition) => {
+                let location = self.debugger.locate(addr);
+                match &condition {
+                    Some(condition) => println!("Breakpoint at {} if {}", location, condition),
+                    None => println!("Breakpoint at {}", location),
                 }
+                self.debugger.add_breakpoint(addr, condition);
             }
-            (0x1, _, _, _) => {
-                self.loaded_ram.PC = nnn as usize;
-            }
-            (0x2, _, _, _) => {
-                self.loaded_ram.store_addr(self.loaded_ram.PC);
-                self.loaded_ram.PC = nnn as usize;
+            Command::Delete(addr) => {
+                if !self.debugger.remove_breakpoint(addr) {
+                    println!("No breakpoint at {}", self.debugger.locate(addr));
+                }
             }
-            (0x3, _, _, _) => {
-                if self.loaded_ram.V[x as usize] == nn {
-                    self.loaded_ram.PC += 2;
+            Command::Watch(target, condition) => {
+                match &condition {
+                    Some(condition) => println!("Watching {} if {}", target, condition),
+                    None => println!("Watching {}", target),
                 }
+                self.debugger.add_watchpoint(target, condition, &self.chip8);
             }
-            (0x4, _, _, _) => {
-                if self.loaded_ram.V[x as usize] != nn {
-                    self.loaded_ram.PC += 2;
+            Command::Unwatch(target) => {
+                if !self.debugger.remove_watchpoint(target) {
+                    println!("{} isn't being watched", target);
                 }
             }
-            (0x5, _, _, 0) => {
-                if self.loaded_ram.V[x as usize] == self.loaded_ram.V[y as usize] {
-                    self.loaded_ram.PC += 2;
+            Command::Step(count) => {
+                match self.debugger.step(&mut self.chip8, count) {
+                    Ok(Some(stop)) => println!("{}", self.debugger.describe(&stop)),
+                    Ok(None) => (),
+                    Err(err) => self.handle_error(err),
                 }
+                self.sync_frontend();
+                self.print_location();
             }
-            (0x6, _, _, _) => self.loaded_ram.set_register(x as usize, nn),
-            (0x7, _, _, _) => {
-                let (updated, _) = self.loaded_ram.V[x as usize].overflowing_add(nn);
-                self.loaded_ram.V[x as usize] = updated;
+            Command::Continue => {
+                self.debugger.mark_memory(&self.chip8);
+                self.game_mode = GameMode::Standard;
             }
-            (0x8, _, _, _) => match n {
-                0 => self.loaded_ram.V[x as usize] = self.loaded_ram.V[y as usize],
-                1 => self.loaded_ram.V[x as usize] |= self.loaded_ram.V[y as usize],
-                2 => self.loaded_ram.V[x as usize] &= self.loaded_ram.V[y as usize],
-                3 => self.loaded_ram.V[x as usize] ^= self.loaded_ram.V[y as usize],
-                4 => {
-                    let (updated, did_overflow) = self.loaded_ram.V[x as usize]
-                        .overflowing_add(self.loaded_ram.V[y as usize]);
-                    self.loaded_ram.V[x as usize] = updated;
-                    self.loaded_ram.update_vf_register(did_overflow);
-                }
-                5 => {
-                    self.loaded_ram.update_vf_register(
-                        self.loaded_ram.V[x as usize] > self.loaded_ram.V[y as usize],
-                    );
-                    let (updated, _) = self.loaded_ram.V[x as usize]
-                        .overflowing_sub(self.loaded_ram.V[y as usize]);
-                    self.loaded_ram.V[x as usize] = updated;
+            Command::Print(target) => {
+                println!("{} = {:#X}", target, target.read(&self.chip8));
+            }
+            Command::Examine { addr, len, layout } => {
+                println!("{}", self.debugger.examine(&self.chip8, addr, len, layout));
+            }
+            Command::Set(target, value) => {
+                if let Err(err) = target.write(&mut self.chip8, value) {
+                    println!("{}", err);
                 }
-                6 => {
-                    // value that will be shifted out - first big end bit
-                    let is_one = self.loaded_ram.V[x as usize] & 1 == 1;
-                    self.loaded_ram.update_vf_register(is_one);
-                    self.loaded_ram.V[x as usize] >>= 1;
+            }
+            Command::Trace(Some(path)) => match Trace::create(Path::new(&path)) {
+                Ok(trace) => {
+                    self.debugger.start_trace(trace);
+                    println!("Tracing to {}", path);
                 }
-                7 => {
-                    self.loaded_ram.update_vf_register(
-                        self.loaded_ram.V[y as usize] > self.loaded_ram.V[x as usize],
-                    );
-                    let (updated, _) = self.loaded_ram.V[y as usize]
-                        .overflowing_sub(self.loaded_ram.V[x as usize]);
-                    self.loaded_ram.V[x as usize] = updated;
+                Err(err) => println!("Can't trace to {} - {}", path, err),
+            },
+            Command::Trace(None) => {
+                if !self.debugger.stop_trace() {
+                    println!("Not tracing");
                 }
-                14 => {
-                    let is_one = self.loaded_ram.V[x as usize] >> 7 == 1;
-                    // value that will be shifted out - final big end bit
-                    self.loaded_ram.update_vf_register(is_one);
-                    self.loaded_ram.V[x as usize] <<= 1;
+            }
+            Command::Gdb(port) => match GdbStub::bind(port) {
+                Ok(stub) => {
+                    if let Ok(addr) = stub.local_addr() {
+                        println!("Waiting for GDB on {} - target remote {}", addr, addr);
+                    }
+                    self.gdb = Some(stub);
+                    self.game_mode = GameMode::Standard;
                 }
-                _ => println!("MISS::{}", n),
+                Err(err) => println!("Can't listen on port {} - {}", port, err),
             },
-            (0x9, _, _, _) => {
-                if self.loaded_ram.V[x as usize] != self.loaded_ram.V[y as usize] {
-                    self.loaded_ram.PC += 2;
+            Command::Symbols(path) => {
+                match fs::read_to_string(&path).map(|text| text.parse::<Symbols>()) {
+                    Ok(Ok(symbols)) if symbols.is_empty() => println!("No symbols in {}", path),
+                    Ok(Ok(symbols)) => {
+                        println!("Loaded {} symbols from {}", symbols.len(), path);
+                        self.debugger.load_symbols(symbols);
+                    }
+                    Ok(Err(err)) => println!("Can't load {} - {}", path, err),
+                    Err(err) => println!("Can't load {} - {}", path, err),
                 }
             }
-            (0xA, _, _, _) => {
-                self.loaded_ram.set_i_register(nnn);
+            Command::Backtrace => println!("{}", self.debugger.backtrace(&self.chip8)),
+            Command::Profile(output) => {
+                self.debugger.start_profile();
+                self.profile_output = output;
+                println!("Profiling until the game exits or profile off");
             }
-            (0xB, _, _, _) => {
-                self.loaded_ram.PC = (self.loaded_ram.V[0] as u16 + nnn) as usize;
-            }
-            (0xC, _, _, _) => {
-                self.loaded_ram.V[x as usize] = rand::random::<u8>() & nn;
+            Command::ProfileOff => {
+                if !self.report_profile() {
+                    println!("Not profiling");
+                }
             }
-            (0xD, _, _, _) => {
-                let sprite_start_idx = self.loaded_ram.I as usize;
-                let sprite_end_idx = sprite_start_idx + n as usize;
+            Command::Registers => println!("{}", self.chip8.ram),
+            Command::Help => println!("{}", Command::HELP),
+            Command::Quit => self.chip8.halt(),
+        }
+    }
 
-                let sprites = &self.loaded_ram.mem[sprite_start_idx..=sprite_end_idx].to_vec();
-                let coords = (self.loaded_ram.V[x as usize], self.loaded_ram.V[y as usize]);
+    /// prints PC and the instruction it points at
+    fn print_location(&self) {
+        let pc = self.chip8.ram.PC;
+        let instruction = self.chip8.peek_instruction();
+        let (mnemonic, _) = disassembler::disassemble_with(
+            self.chip8.ram.mem.get(pc..).unwrap_or(&[]),
+            self.debugger.symbols(),
+        );
+        println!(
+            "{}: {:04X}  {}",
+            self.debugger.locate(pc),
+            instruction.opcode(),
+            mnemonic
+        );
+    }
 
-                let flipped_bit_callback = |did_flip: bool| {
-                    self.loaded_ram.update_vf_register(did_flip);
-                };
+    /// stops profiling and saves or prints the report. Returns false if
+    /// there was no profile running.
+    fn report_profile(&mut self) -> bool {
+        let Some(profiler) = self.debugger.stop_profile() else {
+            return false;
+        };
 
-                let draw_info = DrawInfo {
-                    coords,
-                    sprites: sprites.as_slice(),
-                    row_count: n,
-                };
+        let report = profiler.report(self.debugger.symbols());
+        match self.profile_output.take() {
+            Some(path) => match report.save(Path::new(&path)) {
+                Ok(()) => println!("Saved the profile -> {}", path),
+                Err(err) => println!("Saving the profile to {} failed - {}", path, err),
+            },
+            None => print!("{}", report),
+        }
+
+        true
+    }
 
-                self.display.draw(draw_info, flipped_bit_callback).unwrap();
+    /// shows or hides the debug panel next to the game
+    fn toggle_panel(&mut self) {
+        self.panel = match self.panel {
+            Some(_) => None,
+            None => Some(vec![]),
+        };
+        // the window changes size, so everything needs drawing again
+        self.chip8.force_redraw();
+    }
+
+    /// presses or releases the hex key mapped to code
+    fn set_key(&mut self, code: Scancode, down: bool) {
+        if let Some(hex) = self.keyboard.to_hex(code) {
+            self.chip8.set_key(hex, down);
+        }
+    }
+
+    /// Ctrl + 0-9 saves to a slot, Alt + 0-9 loads from one.
+    /// Returns true if code was a save slot hotkey.
+    fn handle_save_hotkey(&mut self, code: Scancode, keymod: Mod) -> bool {
+        let slot = match Self::SLOT_KEYS.iter().position(|&key| key == code) {
+            Some(slot) => slot as u8,
+            None => return false,
+        };
+
+        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+            match self.save_slots.save(slot, &self.chip8) {
+                Ok(path) => println!("Saved slot {} -> {}", slot, path.display()),
+                Err(err) => println!("Saving slot {} failed - {}", slot, err),
             }
-            (0xE, _, _, _) => match n {
-                0xE => {
-                    if self.is_pressed(self.loaded_ram.V[x as usize]) {
-                        self.loaded_ram.PC += 2;
-                    }
+        } else if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+            match self.save_slots.load(slot) {
+                Ok(mut chip8) => {
+                    chip8.keep_keys_from(&self.chip8);
+                    self.chip8 = chip8;
+                    self.sync_frontend();
+                    println!("Loaded slot {}", slot);
                 }
-                0x1 => {
-                    if !self.is_pressed(self.loaded_ram.V[x as usize]) {
-                        self.loaded_ram.PC += 2;
-                    }
-                }
-                _ => {
-                    println!(
-                        "Recieved unexpected 0xE operation - O::{} X::{} Y::{} N::{}",
-                        first_nibble, x, y, n
-                    );
-                }
-            },
-            (0xF, _, _, _) => match n {
-                3 => {
-                    let (hundred, ten, one) = Self::get_nums(&self.loaded_ram.V[x as usize]);
-                    let current_idx = self.loaded_ram.I;
-                    self.loaded_ram.mem[current_idx as usize] = hundred;
-                    self.loaded_ram.mem[(current_idx + 1) as usize] = ten;
-                    self.loaded_ram.mem[(current_idx + 2) as usize] = one;
-                }
-                5 => match y {
-                    1 => self
-                        .loaded_ram
-                        .set_timer_register(Timer::Delay, self.loaded_ram.V[x as usize]),
-                    5 => {
-                        let current_index = self.loaded_ram.I as usize;
-                        self.loaded_ram.V[0..=x as usize]
-                            .iter()
-                            .enumerate()
-                            .for_each(|(i, val)| {
-                                self.loaded_ram.mem[current_index + i] = *val;
-                            });
+                Err(err) => println!("Loading slot {} failed - {}", slot, err),
+            }
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// runs a frame once every Scheduler::FRAME_DURATION of real time
+    pub fn cycle(&mut self) {
+        let now = Instant::now();
+        let next_frame = self
+            .last_cycle
+            .map_or(now, |instant| instant + Scheduler::FRAME_DURATION);
+
+        if now >= next_frame {
+            // schedule off the ideal frame time so frames don't drift, unless
+            // we've fallen more than a frame behind (ex. coming out of debug mode)
+            self.last_cycle = if now - next_frame > Scheduler::FRAME_DURATION {
+                Some(now)
+            } else {
+                Some(next_frame)
+            };
+            if self.rewinding {
+                self.rewind_frame();
+            } else {
+                match self.debugger.run_frame(&mut self.chip8) {
+                    Ok(Some(stop)) if self.gdb_connected() => {
+                        println!("{}", self.debugger.describe(&stop));
+                        self.report_to_gdb(|gdb| gdb.stop(GdbStub::trap()));
                     }
-                    6 => {
-                        (self.loaded_ram.I..=self.loaded_ram.I + x as u16)
-                            .enumerate()
-                            .for_each(|(i, addr)| {
-                                self.loaded_ram.V[i] = self.loaded_ram.mem[addr as usize];
-                            });
+                    Ok(Some(stop)) => {
+                        println!("{}", self.debugger.describe(&stop));
+                        self.game_mode = GameMode::Debug;
                     }
-                    _ => (),
-                },
-                7 => {
-                    let current_delay_value = self.loaded_ram.get_timer_register(Timer::Delay);
-                    self.loaded_ram
-                        .set_register(x as usize, current_delay_value);
-                }
-                8 => self
-                    .loaded_ram
-                    .set_timer_register(Timer::Sound, self.loaded_ram.V[x as usize]),
-                9 => {
-                    let char = self.loaded_ram.V[x as usize];
-                    self.loaded_ram.I = (80 + (char * 5)) as u16;
-                }
-                0xA => {
-                    if let event::Event::KeyDown {
-                        scancode: Some(code),
-                        ..
-                    } = self.event_pump.wait_event()
-                    {
-                        if let Some(&val) = self.keyboard.scancode_to_hex.get(&code) {
-                            self.loaded_ram.V[x as usize] = val;
-                        };
+                    Ok(None) => (),
+                    Err(err) if self.gdb_connected() => {
+                        println!("Error - {}", err);
+                        self.report_to_gdb(|gdb| gdb.stop(GdbStub::signal(&err)));
                     }
+                    Err(err) => self.handle_error(err),
                 }
-                0xE => {
-                    let (update, did_overflow) = self
-                        .loaded_ram
-                        .I
-                        .overflowing_add(self.loaded_ram.V[x as usize] as u16);
-                    self.loaded_ram.set_i_register(update);
-                    self.loaded_ram.update_vf_register(did_overflow);
+
+                if self.chip8.is_halted() && self.gdb_connected() {
+                    self.report_to_gdb(GdbStub::exited);
                 }
-                _ => {
-                    println!(
-                        "Recieved unexpected 0xF operation - O::{} X::{} Y::{} N::{}",
-                        first_nibble, x, y, n
-                    );
+
+                if let Err(err) = self.rewind.push(&self.chip8) {
+                    println!("Recording rewind history failed - {}", err);
                 }
-            },
+            }
+
+            self.sync_frontend();
+        }
+    }
+
+    /// answers the GDB client, if there is one. Returns true while the client
+    /// has the machine stopped.
+    fn poll_gdb(&mut self) -> bool {
+        let Some(gdb) = self.gdb.as_mut() else {
+            return false;
+        };
+
+        if let Err(err) = gdb.poll(&mut self.chip8, &mut self.debugger) {
+            println!("GDB connection failed - {}", err);
+            self.gdb = None;
+            return false;
+        }
+
+        let halted = gdb.is_halted();
+        // registers or memory may have been written
+        self.sync_frontend();
+        halted
+    }
+
+    fn gdb_connected(&self) -> bool {
+        self.gdb.as_ref().is_some_and(GdbStub::is_connected)
+    }
+
+    /// sends a stop reply, dropping the stub if the client has gone
+    fn report_to_gdb(&mut self, report: impl FnOnce(&mut GdbStub) -> io::Result<()>) {
+        if let Some(gdb) = self.gdb.as_mut() {
+            if let Err(err) = report(gdb) {
+                println!("GDB connection failed - {}", err);
+                self.gdb = None;
+            }
+        }
+    }
+
+    /// swaps in the machine as it was a frame ago, if there's any history left
+    fn rewind_frame(&mut self) {
+        match self.rewind.pop() {
+            Some(Ok(mut chip8)) => {
+                chip8.keep_keys_from(&self.chip8);
+                self.chip8 = chip8;
+            }
+            Some(Err(err)) => println!("Rewinding failed - {}", err),
+            None => (),
+        }
+    }
+
+    /// reports err and halts, skips or breaks into debug mode per the error policy
+    fn handle_error(&mut self, err: Chip8Error) {
+        println!("Error - {}", err);
+
+        match self.error_policy {
+            ErrorPolicy::Skip if err.is_skippable() => self.chip8.skip_instruction(),
+            ErrorPolicy::Break => {
+                println!("Breaking into debug mode on the failed instruction.");
+                self.game_mode = GameMode::Debug;
+            }
             _ => {
-                println!("MISS::{:?}", instruction_bytes);
+                println!("Halting.");
+                self.chip8.halt();
             }
         }
     }
+
+    /// syncs the window and audio device with the Chip8
+    fn sync_frontend(&mut self) {
+        if self.chip8.is_beeping() {
+            self.sound_system.device.resume();
+        } else if self.sound_system.is_playing() {
+            self.sound_system.device.pause();
+        }
+
+        let panel = self
+            .panel
+            .as_ref()
+            .map(|_| overlay::render(&self.chip8, &self.debugger));
+
+        if self.chip8.take_draw_flag() || panel != self.panel {
+            let display = &self.chip8.display;
+            let game = Frame {
+                bytes: &display.get_raw_bytes(&self.palette),
+                width: display.width() as u32,
+                height: display.height() as u32,
+            };
+            let panel = panel.as_deref().map(|bytes| Frame {
+                bytes,
+                width: overlay::WIDTH,
+                height: overlay::HEIGHT,
+            });
+            self.renderer.render(game, panel).unwrap();
+        }
+        self.panel = panel;
+    }
 }
@@ -0,0 +1,118 @@
+use std::{error, fmt, str::FromStr};
+
+/// Something a ROM did that the machine can't carry out. Every error leaves
+/// PC pointing at the offending instruction so it can be inspected, skipped
+/// or stepped over in the debugger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// the program doesn't fit between 0x200 and the end of memory
+    RomTooLarge { size: usize, max: usize },
+    /// PC ran off the end of memory
+    PcOutOfBounds { pc: usize },
+    /// an opcode none of the supported platforms define
+    UnknownOpcode { pc: usize, opcode: u16 },
+    /// an instruction read or wrote len bytes at addr, past the end of memory
+    MemoryOutOfBounds { pc: usize, addr: usize, len: usize },
+    /// 00EE with nothing on the stack to return to
+    StackUnderflow { pc: usize },
+    /// 2NNN with the stack already Ram::STACK_SIZE calls deep
+    StackOverflow { pc: usize },
+}
+
+impl Chip8Error {
+    /// returns false if there's no instruction to step over
+    pub fn is_skippable(&self) -> bool {
+        !matches!(
+            self,
+            Chip8Error::RomTooLarge { .. } | Chip8Error::PcOutOfBounds { .. }
+        )
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => write!(
+                f,
+                "Game loading failed - {} bytes is larger than the {} byte maximum",
+                size, max
+            ),
+            Chip8Error::PcOutOfBounds { pc } => {
+                write!(f, "PC {:#06X} is past the end of memory", pc)
+            }
+            Chip8Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:#06X}", opcode, pc)
+            }
+            Chip8Error::MemoryOutOfBounds { pc, addr, len } => write!(
+                f,
+                "instruction at {:#06X} accessed {} bytes at {:#06X}, past the end of memory",
+                pc, len, addr
+            ),
+            Chip8Error::StackUnderflow { pc } => {
+                write!(f, "00EE at {:#06X} returned with an empty stack", pc)
+            }
+            Chip8Error::StackOverflow { pc } => {
+                write!(f, "2NNN at {:#06X} called with a full stack", pc)
+            }
+        }
+    }
+}
+
+impl error::Error for Chip8Error {}
+
+/// What the frontend does when stepping returns a Chip8Error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// stop the machine
+    #[default]
+    Halt,
+    /// step over the offending instruction and keep running
+    Skip,
+    /// pause in debug mode at the offending instruction
+    Break,
+}
+
+impl ErrorPolicy {
+    pub const POLICIES: [&'static str; 3] = ["halt", "skip", "break"];
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = crate::Error;
+
+    /// parses a policy name - see ErrorPolicy::POLICIES
+    fn from_str(policy: &str) -> crate::Result<Self> {
+        match policy.to_lowercase().as_str() {
+            "halt" => Ok(ErrorPolicy::Halt),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "break" | "debug" => Ok(ErrorPolicy::Break),
+            _ => Err(format!(
+                "unknown error policy '{}' - expected one of {:?}",
+                policy,
+                Self::POLICIES
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!("halt".parse::<ErrorPolicy>().unwrap(), ErrorPolicy::Halt);
+        assert_eq!("Skip".parse::<ErrorPolicy>().unwrap(), ErrorPolicy::Skip);
+        assert_eq!("break".parse::<ErrorPolicy>().unwrap(), ErrorPolicy::Break);
+        assert!("nope".parse::<ErrorPolicy>().is_err());
+    }
+
+    #[test]
+    fn describes_errors() {
+        let err = Chip8Error::UnknownOpcode {
+            pc: 0x2A4,
+            opcode: 0x5121,
+        };
+        assert_eq!(err.to_string(), "unknown opcode 5121 at 0x02A4");
+    }
+}
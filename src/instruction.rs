@@ -1,5 +1,3 @@
-use std::fmt;
-
 pub struct Instruction {
     instruction_bytes: [u8; 2],
     pub first_nibble: u8,
@@ -34,19 +32,9 @@ impl From<[u8; 2]> for Instruction {
     }
 }
 
-impl fmt::Display for Instruction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "----------------------")?;
-        writeln!(f, "     Instruction      ")?;
-        writeln!(f, "----------------------")?;
-        writeln!(f, "Instruction Bytes: {:?}", self.instruction_bytes)?;
-        writeln!(f, "OP: {:x}", self.first_nibble)?;
-        writeln!(f, "X: {:x}", self.x)?;
-        writeln!(f, "Y: {:x}", self.y)?;
-        writeln!(f, "N: {:x}", self.n)?;
-        writeln!(f, "NN: {:x}", self.nn)?;
-        writeln!(f, "NNN: {:x}", self.nnn)?;
-
-        Ok(())
+impl Instruction {
+    /// the raw two byte opcode
+    pub fn opcode(&self) -> u16 {
+        u16::from_be_bytes(self.instruction_bytes)
     }
 }
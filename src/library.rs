@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cli::game;
+use crate::rom_db::RomDb;
+use crate::Result;
+
+/// the demo games, copied into a new library so it isn't empty
+const BUNDLED: [(&str, &[u8]); 4] = [
+    ("Airplane.ch8", include_bytes!("../games/Airplane.ch8")),
+    ("Astro.ch8", include_bytes!("../games/Astro.ch8")),
+    ("Ibm.ch8", include_bytes!("../games/Ibm.ch8")),
+    (
+        "test_opcode.ch8",
+        include_bytes!("../games/test_opcode.ch8"),
+    ),
+];
+
+/// ROMs under the library's `roms` directory (subdirectories included),
+/// with what the ROM database knows about them and how they've been played.
+/// Play stats are kept in `library.json` beside it, keyed by SHA-1, so
+/// moving or renaming a ROM keeps them.
+pub struct Library {
+    dir: PathBuf,
+    stats: BTreeMap<String, Stats>,
+    pub games: Vec<Game>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    /// seconds since the unix epoch
+    pub last_played: Option<u64>,
+    /// seconds
+    pub play_time: u64,
+    pub favorite: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub path: PathBuf,
+    pub hash: String,
+    pub title: String,
+    /// from the ROM database, None if it's not in there
+    pub platform: Option<String>,
+    pub stats: Stats,
+}
+
+/// orders for Library::list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Title,
+    LastPlayed,
+    PlayTime,
+    Favorites,
+}
+
+impl Sort {
+    pub const ALL: [Sort; 4] = [
+        Sort::Title,
+        Sort::LastPlayed,
+        Sort::PlayTime,
+        Sort::Favorites,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Sort::Title => "Title",
+            Sort::LastPlayed => "Recently played",
+            Sort::PlayTime => "Most played",
+            Sort::Favorites => "Favorites first",
+        }
+    }
+}
+
+impl Library {
+    /// file extensions the scan picks up as ROMs
+    pub const EXTENSIONS: [&'static str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+    /// the user's data directory (Ex. ~/.local/share/chippy)
+    pub fn default_dir() -> PathBuf {
+        let data_dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+        data_dir.join("chippy")
+    }
+
+    /// the library under the default directory
+    pub fn open() -> Result<Self> {
+        Self::open_at(Self::default_dir())
+    }
+
+    /// scans dir/roms, creating it with the demo games if it's missing
+    pub fn open_at(dir: PathBuf) -> Result<Self> {
+        let roms = dir.join("roms");
+        if !roms.exists() {
+            fs::create_dir_all(&roms)?;
+            for (name, program) in BUNDLED {
+                fs::write(roms.join(name), program)?;
+            }
+        }
+
+        let stats_path = dir.join("library.json");
+        let stats: BTreeMap<String, Stats> = if stats_path.exists() {
+            serde_json::from_str(&fs::read_to_string(&stats_path)?)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut paths = vec![];
+        scan(&roms, &mut paths)?;
+
+        let games = paths
+            .into_iter()
+            .map(|path| {
+                let hash = game::hash(&fs::read(&path)?);
+                let info = RomDb::bundled().get(&hash);
+                let title = info.map_or_else(
+                    || {
+                        path.file_stem()
+                            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+                    },
+                    |info| info.title.clone(),
+                );
+
+                Ok(Game {
+                    title,
+                    platform: info.map(|info| info.platform.clone()),
+                    stats: stats.get(&hash).cloned().unwrap_or_default(),
+                    hash,
+                    path,
+                })
+            })
+            .collect::<Result<Vec<Game>>>()?;
+
+        Ok(Library { dir, stats, games })
+    }
+
+    pub fn roms_dir(&self) -> PathBuf {
+        self.dir.join("roms")
+    }
+
+    /// games with filter in their title, platform or file name, sorted
+    pub fn list(&self, filter: &str, sort: Sort) -> Vec<&Game> {
+        let filter = filter.trim().to_lowercase();
+        let mut games = self
+            .games
+            .iter()
+            .filter(|game| {
+                [
+                    game.title.as_str(),
+                    game.platform.as_deref().unwrap_or(""),
+                    &game
+                        .path
+                        .file_name()
+                        .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+                ]
+                .iter()
+                .any(|field| field.to_lowercase().contains(&filter))
+            })
+            .collect::<Vec<&Game>>();
+
+        games.sort_by_key(|game| game.title.to_lowercase());
+        // stable, so ties stay in title order
+        match sort {
+            Sort::Title => (),
+            Sort::LastPlayed => games.sort_by_key(|game| std::cmp::Reverse(game.stats.last_played)),
+            Sort::PlayTime => games.sort_by_key(|game| std::cmp::Reverse(game.stats.play_time)),
+            Sort::Favorites => games.sort_by_key(|game| !game.stats.favorite),
+        }
+
+        games
+    }
+
+    /// returns whether the game with hash is now a favorite
+    pub fn toggle_favorite(&mut self, hash: &str) -> Result<bool> {
+        let favorite = self.update(hash, |stats| stats.favorite = !stats.favorite)?;
+        Ok(favorite.favorite)
+    }
+
+    /// adds a session of duration, ending now, to the game's stats
+    pub fn record_play(&mut self, hash: &str, duration: Duration) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.update(hash, |stats| {
+            stats.last_played = Some(now);
+            stats.play_time += duration.as_secs();
+        })?;
+        Ok(())
+    }
+
+    fn update(&mut self, hash: &str, change: impl FnOnce(&mut Stats)) -> Result<Stats> {
+        let stats = self.stats.entry(hash.to_string()).or_default();
+        change(stats);
+        let stats = stats.clone();
+
+        for game in self.games.iter_mut().filter(|game| game.hash == hash) {
+            game.stats = stats.clone();
+        }
+        fs::write(
+            self.dir.join("library.json"),
+            serde_json::to_string_pretty(&self.stats)?,
+        )?;
+
+        Ok(stats)
+    }
+}
+
+/// collects the ROMs under dir, depth first
+fn scan(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            scan(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| {
+            Library::EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        }) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Game {
+    /// a line for the menu, Ex. "* Astro Dodge  [vip]  played 12m, 2 days ago"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let star = if self.stats.favorite { '*' } else { ' ' };
+        write!(f, "{} {}", star, self.title)?;
+        if let Some(platform) = &self.platform {
+            write!(f, "  [{}]", platform)?;
+        }
+
+        match self.stats.last_played {
+            Some(last_played) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs());
+                write!(
+                    f,
+                    "  played {}, {}",
+                    format_play_time(self.stats.play_time),
+                    format_days_ago(now.saturating_sub(last_played) / 86_400)
+                )
+            }
+            None => write!(f, "  never played"),
+        }
+    }
+}
+
+/// Ex. "1h 5m", "<1m"
+fn format_play_time(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60) {
+        (0, 0) => "<1m".to_string(),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn format_days_ago(days: u64) -> String {
+    match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chippy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn scans_recursively_and_seeds_new_libraries() {
+        let dir = library_dir("library-scan");
+        let library = Library::open_at(dir.clone()).unwrap();
+        assert_eq!(library.games.len(), BUNDLED.len());
+
+        let nested = library.roms_dir().join("schip").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("Blinky.SC8"), [0x00, 0xFF]).unwrap();
+        fs::write(nested.join("notes.txt"), "not a rom").unwrap();
+
+        let library = Library::open_at(dir.clone()).unwrap();
+        assert_eq!(library.games.len(), BUNDLED.len() + 1);
+        assert!(library.games.iter().any(|game| game.title == "Blinky"));
+        assert!(library.games.iter().any(|game| game.title == "Astro Dodge"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_stats_and_sorts() {
+        let dir = library_dir("library-stats");
+        let mut library = Library::open_at(dir.clone()).unwrap();
+        let ibm = game::hash(include_bytes!("../games/Ibm.ch8"));
+        let airplane = game::hash(include_bytes!("../games/Airplane.ch8"));
+
+        assert!(library.toggle_favorite(&ibm).unwrap());
+        library
+            .record_play(&airplane, Duration::from_secs(3900))
+            .unwrap();
+
+        // stats survive reopening
+        let library = Library::open_at(dir.clone()).unwrap();
+        let titles = |sort| {
+            library
+                .list("", sort)
+                .iter()
+                .map(|game| game.title.as_str())
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!(
+            titles(Sort::Title),
+            ["Airplane", "Astro Dodge", "IBM Logo", "Opcode Test"]
+        );
+        assert_eq!(titles(Sort::Favorites)[0], "IBM Logo");
+        assert_eq!(titles(Sort::PlayTime)[0], "Airplane");
+        assert_eq!(titles(Sort::LastPlayed)[0], "Airplane");
+
+        let played = library.list("airPLANE", Sort::Title);
+        assert_eq!(played.len(), 1);
+        assert!(played[0].to_string().contains("played 1h 5m, today"));
+        assert_eq!(library.list("vip", Sort::Title).len(), 4);
+        assert!(library.list("pong", Sort::Title).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
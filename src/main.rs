@@ -1,8 +1,23 @@
+mod assembler;
+mod chip8;
 mod cli;
+mod config;
+mod debugger;
+mod disassembler;
 mod display;
+mod download;
 mod emulator;
+mod error;
 mod instruction;
+mod library;
+mod quirks;
 mod ram;
+mod rewind;
+mod rng;
+mod rom_db;
+mod save_state;
+mod scheduler;
+mod symbols;
 mod sys_handles;
 
 extern crate dialoguer;
@@ -10,20 +25,39 @@ extern crate rand;
 extern crate reqwest;
 extern crate sdl2;
 
-use std::{error, fs, result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    result,
+    time::Instant,
+};
+
+use clap::Parser;
 
 use crate::cli::{
-    game::{Loadable, LocalGame, RemoteGame},
+    args::{Args, Command},
+    game::{self, Loadable, LocalGame},
     question::Question,
 };
-use crate::emulator::{Emulator, GameMode};
+use crate::config::Config;
+use crate::debugger::command::Command as DebuggerCommand;
+use crate::download::Downloader;
+use crate::emulator::{Emulator, GameMode, Settings};
+use crate::error::ErrorPolicy;
+use crate::library::{Library, Sort};
+use crate::quirks::Quirks;
+use crate::rom_db::RomDb;
+use crate::symbols::Symbols;
 
-pub type Error = Box<dyn error::Error>;
+pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = result::Result<T, Error>;
 
 fn main() -> Result<()> {
     const MENU_OPTIONS: [&str; 3] = ["Select Game", "Load Local Game", "Download Remote Game"];
     const GAME_MODE_OPTIONS: [&str; 2] = ["Standard", "Debug"];
+    const QUIRK_OPTIONS: [&str; 4] = ["COSMAC VIP", "CHIP-48", "SUPER-CHIP", "XO-CHIP"];
+    const ERROR_POLICY_OPTIONS: [&str; 3] =
+        ["Halt", "Skip the instruction", "Break into debug mode"];
     const INTRO: &str = "__________________________________________________________
       __                                                  
     /    )    /      ,                     ,           /  
@@ -33,49 +67,81 @@ _(____/____/___/__/______/___/__|/_|/___/____(___ __/___/_
                         /                                 
                        /\n A rustaceous chip8 emulator\n\n";
 
-    sdl2::hint::set("SDL_NO_SIGNAL_HANDLERS", "1");
-
-    let games = fs::read_dir("games")?
-        .filter_map(|entry| {
-            if let Ok(entry) = entry {
-                entry.file_name().to_str().map(|name| name.to_owned())
-            } else {
-                None
+    let args = Args::parse();
+    match args.command {
+        Some(Command::Run(run)) => {
+            let program = load(&run.rom, run.sha1.as_deref())?;
+            let settings = run.settings(Config::load()?.settings(&program)?);
+            return start_emulator(program, settings, run.commands);
+        }
+        Some(Command::Asm {
+            source,
+            output,
+            symbols,
+        }) => {
+            let assembly = assembler::assemble(&fs::read_to_string(&source)?)?;
+            let program = assembly.program;
+            let output = output.unwrap_or_else(|| {
+                Path::new(&source)
+                    .with_extension("ch8")
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            fs::write(&output, &program)?;
+            println!("Assembled {} bytes -> {}", program.len(), output);
+            if let Some(path) = symbols {
+                fs::write(&path, assembly.labels.to_string())?;
+                println!("Wrote {} labels -> {}", assembly.labels.len(), path);
             }
-        })
-        .collect::<Vec<String>>();
-
-    let available_games: Vec<&str> = games.iter().map(|val| val.as_str()).collect::<Vec<&str>>();
+            return Ok(());
+        }
+        Some(Command::Disasm { rom, symbols }) => {
+            let symbols = match symbols {
+                Some(path) => fs::read_to_string(path)?.parse()?,
+                None => Symbols::default(),
+            };
+            print!(
+                "{}",
+                disassembler::listing(&LocalGame::load(&rom)?, &symbols)
+            );
+            return Ok(());
+        }
+        None => {
+            if let Some(rom) = args.rom {
+                let program = load(&rom, None)?;
+                let settings = Config::load()?.settings(&program)?;
+                return start_emulator(program, settings, vec![]);
+            }
+        }
+    }
 
     println!("{INTRO}");
 
     if let Ok(Some(idx)) = Question::select(&MENU_OPTIONS, Some("Make a Selection"), Some(&0)) {
         match idx {
             0 => {
-                if let Ok(Some(idx)) =
-                    Question::select(&available_games, Some("Choose a game"), Some(&0))
-                {
-                    let selected = available_games.get(idx).unwrap();
-                    let path = format!("games/{}", selected);
-                    let program = LocalGame::load(path.as_str())?;
-                    let game_mode = get_game_mode();
-                    start_emulator(program, game_mode);
+                let mut library = Library::open()?;
+                if let Some(path) = pick_game(&mut library)? {
+                    let program = LocalGame::load(&path.to_string_lossy())?;
+                    let settings = ask_settings(&program)?;
+                    start_emulator(program, settings, vec![])?;
                 }
             },
             1 => {
                 if let Ok(file_path) = Question::input((Some("Type in the path to the game\n This should be an absolute file path. (Ex. /Users/SomeUser/documents/games/blah.ch8)"), None, None)) {
                     let program = LocalGame::load(file_path.as_str())?;
-                    let game_mode = get_game_mode();
-                    start_emulator(program, game_mode);
+                    let settings = ask_settings(&program)?;
+                    start_emulator(program, settings, vec![])?;
                 }
             },
             2 => {
                 if let Ok(url) = Question::input((Some("Type in the url of the game to download."), None, None)) {
-                    println!("Downloading -> {}", &url);
+                    let sha1 = Question::input((Some("Expected SHA-1, to check the download against. Leave blank to skip."), None, Some("")))?;
+                    let sha1 = Some(sha1.trim()).filter(|sha1| !sha1.is_empty());
 
-                    let program = RemoteGame::load(&url)?;
-                    let game_mode = get_game_mode();
-                    start_emulator(program, game_mode);
+                    let program = load(&url, sha1)?;
+                    let settings = ask_settings(&program)?;
+                    start_emulator(program, settings, vec![])?;
                 }
             },
 
@@ -83,19 +149,135 @@ _(____/____/___/__/______/___/__|/_|/___/____(___ __/___/_
         }
     }
 
-    fn start_emulator(program: Vec<u8>, game_mode: GameMode) {
-        if game_mode == GameMode::Debug {
+    /// downloads rom if it's a URL (or takes it from the download cache),
+    /// otherwise reads it from disk. Fails if sha1 is given and doesn't match.
+    fn load(rom: &str, sha1: Option<&str>) -> Result<Vec<u8>> {
+        if rom.starts_with("http://") || rom.starts_with("https://") {
+            println!("Downloading -> {}", rom);
+            let download = Downloader::for_library()?.fetch(rom, sha1)?;
+            if download.cached {
+                println!("Already downloaded -> {}", download.path.display());
+            } else {
+                println!("Saved to the library -> {}", download.path.display());
+            }
+            return Ok(download.program);
+        }
+
+        let program = LocalGame::load(rom)?;
+        if let Some(expected) = sha1 {
+            let hash = game::hash(&program);
+            if !hash.eq_ignore_ascii_case(expected) {
+                return Err(format!("{} has SHA-1 {}, expected {}", rom, hash, expected).into());
+            }
+        }
+        Ok(program)
+    }
+
+    /// boots the game and runs commands in the debugger before starting it
+    fn start_emulator(
+        program: Vec<u8>,
+        settings: Settings,
+        commands: Vec<DebuggerCommand>,
+    ) -> Result<()> {
+        sdl2::hint::set("SDL_NO_SIGNAL_HANDLERS", "1");
+
+        if let Some(info) = RomDb::bundled().get(&game::hash(&program)) {
+            let author = info
+                .author
+                .as_deref()
+                .map_or(String::new(), |author| format!(" by {}", author));
+            println!("{}{} ({})", info.title, author, info.platform);
+            if !info.keys.is_empty() {
+                println!("Keys: {}", info.key_hints());
+            }
+        }
+
+        if settings.game_mode == GameMode::Debug {
             println!(
-                "The game is running in debug mode. Hit enter at anytime\n to enter standard mode."
+                "The game is running in debug mode. Type help for a list of debugger\n commands."
             );
         } else {
             println!(
-                "The game is running in standard mode. Hit space at anytime\n to enter debug mode."
+                "The game is running in standard mode. Hit space at anytime\n to break into the debugger."
             );
         }
 
-        let mut emu = Emulator::boot(program, game_mode);
+        let hash = game::hash(&program);
+        let started = Instant::now();
+        let mut emu = Emulator::boot(program, settings)?;
+        for command in commands {
+            emu.run_command(command);
+        }
         emu.start();
+
+        if let Err(err) =
+            Library::open().and_then(|mut library| library.record_play(&hash, started.elapsed()))
+        {
+            println!("Couldn't update the play stats - {}", err);
+        }
+        Ok(())
+    }
+
+    /// asks for a filter and order, then a game from the library, offering
+    /// to favorite it before playing. None if nothing was picked.
+    fn pick_game(library: &mut Library) -> Result<Option<PathBuf>> {
+        let filter = Question::input((
+            Some("Filter by title, platform or file name. Leave blank for all."),
+            None,
+            Some(""),
+        ))?;
+
+        let sorts = Sort::ALL.map(|sort| sort.label());
+        let Some(sort) = Question::select(&sorts, Some("Sort by"), Some(&0))? else {
+            return Ok(None);
+        };
+
+        loop {
+            let games = library.list(&filter, Sort::ALL[sort]);
+            if games.is_empty() {
+                println!(
+                    "No games found - add ROMs to {}",
+                    library.roms_dir().display()
+                );
+                return Ok(None);
+            }
+
+            let lines = games
+                .iter()
+                .map(|game| game.to_string())
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+            let Some(idx) = Question::select(&lines, Some("Choose a game"), Some(&0))? else {
+                return Ok(None);
+            };
+            let game = games[idx].clone();
+
+            let favorite = if game.stats.favorite {
+                "Remove from favorites"
+            } else {
+                "Add to favorites"
+            };
+            match Question::select(&["Play", favorite], Some(&game.title), Some(&0))? {
+                Some(0) => return Ok(Some(game.path)),
+                Some(_) => {
+                    library.toggle_favorite(&game.hash)?;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// asks for the settings the menu covers, defaulting to the config's
+    fn ask_settings(program: &[u8]) -> Result<Settings> {
+        let config = Config::load()?.settings(program)?;
+
+        Ok(Settings {
+            game_mode: get_game_mode(),
+            quirks: get_quirks(config.quirks),
+            ips: get_ips(config.ips),
+            error_policy: get_error_policy(),
+            ..config
+        })
     }
 
     fn get_game_mode() -> GameMode {
@@ -111,5 +293,60 @@ _(____/____/___/__/______/___/__|/_|/___/____(___ __/___/_
         }
     }
 
+    fn get_ips(default: u32) -> u32 {
+        let default_ips = default.to_string();
+
+        match Question::input((
+            Some("Instructions per second. Timers always run at 60Hz."),
+            None,
+            Some(default_ips.as_str()),
+        )) {
+            Ok(ips) => ips.trim().parse().unwrap_or(default),
+            Err(_) => default,
+        }
+    }
+
+    fn get_error_policy() -> ErrorPolicy {
+        if let Ok(Some(idx)) = Question::select(
+            &ERROR_POLICY_OPTIONS,
+            Some("When the game hits an error"),
+            Some(&0),
+        ) {
+            ErrorPolicy::POLICIES[idx].parse().unwrap_or_default()
+        } else {
+            ErrorPolicy::default()
+        }
+    }
+
+    /// starts on default's preset if it is one
+    fn get_quirks(default: Quirks) -> Quirks {
+        let preset = Quirks::PRESETS
+            .iter()
+            .position(|preset| preset.parse().ok() == Some(default))
+            .unwrap_or(0);
+
+        let mut quirks = if let Ok(Some(idx)) = Question::select(
+            &QUIRK_OPTIONS,
+            Some("Select a quirk profile"),
+            Some(&preset),
+        ) {
+            Quirks::PRESETS[idx].parse().unwrap_or_default()
+        } else {
+            default
+        };
+
+        if let Ok(overrides) = Question::input((
+            Some("Quirk overrides (Ex. wrap=true,shift=false). Leave blank for none."),
+            None,
+            Some(""),
+        )) {
+            if let Err(err) = quirks.apply_overrides(&overrides) {
+                println!("Ignoring quirk overrides - {}", err);
+            }
+        }
+
+        quirks
+    }
+
     Ok(())
 }
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::ram::Ram;
+use crate::Result;
+
+/// Behaviors that differ between CHIP-8 interpreters. ROMs are written
+/// against one interpreter or another, so the wrong set makes them
+/// misbehave or render garbage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55/FX65 leave I pointing past the last register stored/loaded
+    pub load_store_increments_i: bool,
+    /// with load_store_increments_i, I only advances by X, leaving it on the
+    /// last register stored/loaded - CHIP-48's off by one
+    pub load_store_increments_by_x: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+    /// sprites drawn past the edge of the screen wrap instead of clipping
+    pub wrap_sprites: bool,
+    /// 64K of memory instead of 4K
+    pub memory_64k: bool,
+}
+
+impl Quirks {
+    pub const PRESETS: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+    pub const FLAGS: [&'static str; 7] = [
+        "shift",
+        "load_store",
+        "load_store_x",
+        "jump",
+        "vf_reset",
+        "wrap",
+        "mem_64k",
+    ];
+
+    /// the original COSMAC VIP interpreter
+    pub const fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            load_store_increments_by_x: false,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            wrap_sprites: false,
+            memory_64k: false,
+        }
+    }
+
+    /// the HP48 CHIP-48 interpreter
+    pub const fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            load_store_increments_by_x: true,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            wrap_sprites: false,
+            memory_64k: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub const fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            load_store_increments_by_x: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            wrap_sprites: false,
+            memory_64k: false,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo
+    pub const fn xochip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            load_store_increments_by_x: false,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            wrap_sprites: true,
+            memory_64k: true,
+        }
+    }
+
+    /// overrides a single flag by name - see Quirks::FLAGS
+    pub fn set(&mut self, flag: &str, enabled: bool) -> Result<()> {
+        let field = match flag {
+            "shift" => &mut self.shift_uses_vy,
+            "load_store" => &mut self.load_store_increments_i,
+            "load_store_x" => &mut self.load_store_increments_by_x,
+            "jump" => &mut self.jump_uses_vx,
+            "vf_reset" => &mut self.logic_resets_vf,
+            "wrap" => &mut self.wrap_sprites,
+            "mem_64k" => &mut self.memory_64k,
+            _ => {
+                return Err(format!(
+                    "unknown quirk flag '{}' - expected one of {:?}",
+                    flag,
+                    Self::FLAGS
+                )
+                .into())
+            }
+        };
+
+        *field = enabled;
+        Ok(())
+    }
+
+    /// applies comma separated overrides (Ex. "wrap=true,shift=false")
+    pub fn apply_overrides(&mut self, overrides: &str) -> Result<()> {
+        for assignment in overrides.split(',').map(str::trim) {
+            if assignment.is_empty() {
+                continue;
+            }
+
+            let (flag, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("expected flag=true|false, got '{}'", assignment))?;
+
+            self.set(flag.trim(), value.trim().parse()?)?;
+        }
+
+        Ok(())
+    }
+
+    /// bytes of memory the machine has
+    pub fn memory_size(&self) -> usize {
+        if self.memory_64k {
+            Ram::XO_MEM_SIZE
+        } else {
+            Ram::MEM_SIZE
+        }
+    }
+
+    /// a preset optionally followed by overrides, Ex. "schip,wrap=true"
+    pub fn from_spec(spec: &str) -> Result<Self> {
+        let (preset, overrides) = spec.split_once(',').unwrap_or((spec, ""));
+        let mut quirks = preset.trim().parse::<Quirks>()?;
+        quirks.apply_overrides(overrides)?;
+        Ok(quirks)
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = crate::Error;
+
+    /// parses a preset name - see Quirks::PRESETS
+    fn from_str(preset: &str) -> Result<Self> {
+        match preset.to_lowercase().as_str() {
+            "vip" | "chip8" | "cosmac" => Ok(Self::cosmac_vip()),
+            "chip48" => Ok(Self::chip48()),
+            "schip" | "superchip" => Ok(Self::schip()),
+            "xochip" | "octo" => Ok(Self::xochip()),
+            _ => Err(format!(
+                "unknown quirk preset '{}' - expected one of {:?}",
+                preset,
+                Self::PRESETS
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_presets() {
+        assert_eq!("vip".parse::<Quirks>().unwrap(), Quirks::cosmac_vip());
+        assert_eq!("CHIP48".parse::<Quirks>().unwrap(), Quirks::chip48());
+        assert_eq!("schip".parse::<Quirks>().unwrap(), Quirks::schip());
+        assert_eq!("xochip".parse::<Quirks>().unwrap(), Quirks::xochip());
+        assert!("nope".parse::<Quirks>().is_err());
+    }
+
+    #[test]
+    fn overrides_flags() {
+        let mut quirks = Quirks::schip();
+        quirks.set("wrap", true).unwrap();
+        assert!(quirks.wrap_sprites);
+        assert!(quirks.set("nope", true).is_err());
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let mut quirks = Quirks::cosmac_vip();
+        quirks.apply_overrides("wrap=true, shift=false").unwrap();
+        assert!(quirks.wrap_sprites);
+        assert!(!quirks.shift_uses_vy);
+        assert!(quirks.apply_overrides("wrap").is_err());
+        assert!(quirks.apply_overrides("wrap=maybe").is_err());
+    }
+
+    #[test]
+    fn parses_specs() {
+        let quirks = Quirks::from_spec("schip, wrap=true").unwrap();
+        assert!(quirks.wrap_sprites);
+        assert!(!quirks.shift_uses_vy);
+        assert_eq!(Quirks::from_spec("xochip").unwrap(), Quirks::xochip());
+        assert!(Quirks::from_spec("nes,wrap=true").is_err());
+    }
+}
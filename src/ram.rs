@@ -1,15 +1,19 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::error::Chip8Error;
+
 #[derive(Debug)]
 pub enum Timer {
     Delay,
     Sound,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct Ram {
-    pub mem: [u8; 4096],
+    /// MEM_SIZE bytes, or XO_MEM_SIZE on XO-CHIP - see Quirks::memory_size
+    pub mem: Vec<u8>,
     pub PC: usize,
     pub V: [u8; 16],
     pub I: u16,
@@ -64,24 +68,64 @@ impl Ram {
         0xF0, 0x80, 0xF0, 0x80, 0x80, // F
     ];
 
+    /// SUPER-CHIP 8x10 digits
+    const BIG_FONT: [u8; 160] = [
+        0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+        0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+        0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+        0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+        0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+        0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+        0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+        0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+    ];
+
+    pub const FONT_START: usize = 80;
+    pub const BIG_FONT_START: usize = Self::FONT_START + Self::FONT.len();
     pub const START_PRGM_REGISTER: usize = 0x200;
-    /// creates Ram struct
-    pub fn load(program: &[u8]) -> Self {
-        let memory = [0; 4096];
+    /// deepest the call stack goes, as on SUPER-CHIP
+    pub const STACK_SIZE: usize = 16;
+    /// 4K, like the COSMAC VIP
+    pub const MEM_SIZE: usize = 0x1000;
+    /// XO-CHIP's 64K
+    pub const XO_MEM_SIZE: usize = 0x10000;
+    /// the largest program any platform can load
+    pub const MAX_PROGRAM_SIZE: usize = Self::XO_MEM_SIZE - Self::START_PRGM_REGISTER;
+
+    /// creates Ram struct with mem_size bytes of memory - see Quirks::memory_size
+    pub fn load(program: &[u8], mem_size: usize) -> Result<Self, Chip8Error> {
+        let memory = vec![0; mem_size];
         let program_len = program.len();
+        let max = mem_size - Self::START_PRGM_REGISTER;
+
+        if program_len > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: program_len,
+                max,
+            });
+        }
 
-        let loaded = memory[0..80]
+        let font_end = Self::BIG_FONT_START + Self::BIG_FONT.len();
+
+        let loaded = memory[0..Self::FONT_START]
             .iter()
             .chain(Self::FONT.iter())
-            .chain(memory[(80 + Self::FONT.len())..Self::START_PRGM_REGISTER].iter())
+            .chain(Self::BIG_FONT.iter())
+            .chain(memory[font_end..Self::START_PRGM_REGISTER].iter())
             .chain(program.iter())
             .chain(memory[Self::START_PRGM_REGISTER + program_len..].iter())
             .copied()
-            .collect::<Vec<u8>>()
-            .try_into()
-            .expect("Game loading failed");
+            .collect::<Vec<u8>>();
 
-        Ram {
+        Ok(Ram {
             mem: loaded,
             stack: vec![],
             V: [0; 16],
@@ -89,17 +133,19 @@ impl Ram {
             PC: Self::START_PRGM_REGISTER,
             delay_timer: 0,
             sound_timer: 0,
-        }
+        })
     }
 
-    pub fn get_next_instruction(&mut self) -> [u8; 2] {
-        let hi_byte = self.mem[self.PC as usize];
-        let lo_byte = self.mem[self.PC + 1_usize];
+    pub fn get_next_instruction(&mut self) -> Result<[u8; 2], Chip8Error> {
+        let (hi_byte, lo_byte) = match self.mem.get(self.PC..self.PC + 2) {
+            Some(&[hi_byte, lo_byte]) => (hi_byte, lo_byte),
+            _ => return Err(Chip8Error::PcOutOfBounds { pc: self.PC }),
+        };
 
         // Move PC to next instruction. We consume instructions
         // two bytes at a time.
         self.PC += 2;
-        [hi_byte, lo_byte]
+        Ok([hi_byte, lo_byte])
     }
 
     /// sets VF register
@@ -117,9 +163,13 @@ impl Ram {
         self.V[idx] = value;
     }
 
-    /// adds address to Ram.stack
-    pub fn store_addr(&mut self, addr: usize) {
+    /// adds address to Ram.stack, returning false if it's already full
+    pub fn store_addr(&mut self, addr: usize) -> bool {
+        if self.stack.len() >= Self::STACK_SIZE {
+            return false;
+        }
         self.stack.push(addr);
+        true
     }
 
     /// removes most recently added memory address from Ram.stack
@@ -154,35 +204,85 @@ mod tests {
 
     #[test]
     fn loads_font() {
-        let ram = Ram::load(&PROGRAM);
-        let end = 80 + Ram::FONT.len();
-        assert_eq!(&ram.mem[80..end], Ram::FONT);
+        let ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
+        let end = Ram::FONT_START + Ram::FONT.len();
+        assert_eq!(&ram.mem[Ram::FONT_START..end], Ram::FONT);
+    }
+
+    #[test]
+    fn loads_big_font() {
+        let ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
+        let end = Ram::BIG_FONT_START + Ram::BIG_FONT.len();
+        assert_eq!(&ram.mem[Ram::BIG_FONT_START..end], Ram::BIG_FONT);
     }
 
     #[test]
     fn loads_program() {
-        let ram = Ram::load(&PROGRAM);
+        let ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         let end = ram.PC + PROGRAM.len();
         assert_eq!(&ram.mem[ram.PC..end], PROGRAM);
     }
 
+    #[test]
+    fn loads_large_program() {
+        let program = vec![0xAB; 0x8000];
+        let ram = Ram::load(&program, Ram::XO_MEM_SIZE).unwrap();
+        assert_eq!(ram.mem.len(), Ram::XO_MEM_SIZE);
+        assert_eq!(ram.mem[Ram::START_PRGM_REGISTER + 0x7FFF], 0xAB);
+    }
+
+    #[test]
+    fn rejects_oversized_program() {
+        let program = vec![0; Ram::MAX_PROGRAM_SIZE + 1];
+        assert!(matches!(
+            Ram::load(&program, Ram::XO_MEM_SIZE),
+            Err(Chip8Error::RomTooLarge { .. })
+        ));
+
+        // 4K machines fit a lot less
+        let program = vec![0; Ram::MEM_SIZE - Ram::START_PRGM_REGISTER + 1];
+        assert_eq!(
+            Ram::load(&program, Ram::MEM_SIZE).err(),
+            Some(Chip8Error::RomTooLarge {
+                size: 0xE01,
+                max: 0xE00
+            })
+        );
+    }
+
+    #[test]
+    fn stops_at_end_of_memory() {
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
+        ram.PC = Ram::MEM_SIZE - 1;
+        assert_eq!(
+            ram.get_next_instruction(),
+            Err(Chip8Error::PcOutOfBounds {
+                pc: Ram::MEM_SIZE - 1
+            })
+        );
+    }
+
     #[test]
     fn sets_registers() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.set_register(1, 10u8);
         assert_eq!(ram.V[1], 10u8)
     }
 
     #[test]
     fn adds_address() {
-        let mut ram = Ram::load(&PROGRAM);
-        ram.store_addr(0x0F);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
+        assert!(ram.store_addr(0x0F));
         assert_eq!(ram.stack, vec![0x0F]);
+
+        (1..Ram::STACK_SIZE).for_each(|_| assert!(ram.store_addr(0x0F)));
+        assert!(!ram.store_addr(0x10));
+        assert_eq!(ram.stack.len(), Ram::STACK_SIZE);
     }
 
     #[test]
     fn removes_address() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.store_addr(0x0F);
         let removed = ram.remove_addr().unwrap();
         assert!(removed == 0x0F);
@@ -190,7 +290,7 @@ mod tests {
 
     #[test]
     fn updates_vf_register() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.update_vf_register(true);
         assert!(ram.V[15] == 1);
         ram.update_vf_register(false);
@@ -199,28 +299,28 @@ mod tests {
 
     #[test]
     fn sets_delay_timer_register() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.set_timer_register(Timer::Delay, 255);
         assert_eq!(ram.delay_timer, 255);
     }
 
     #[test]
     fn gets_delay_timer_register() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.set_timer_register(Timer::Delay, 255);
         assert_eq!(255, ram.get_timer_register(Timer::Delay));
     }
 
     #[test]
     fn sets_sound_timer_register() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.set_timer_register(Timer::Sound, 255);
         assert_eq!(ram.sound_timer, 255);
     }
 
     #[test]
     fn gets_sound_timer_register() {
-        let mut ram = Ram::load(&PROGRAM);
+        let mut ram = Ram::load(PROGRAM, Ram::MEM_SIZE).unwrap();
         ram.set_timer_register(Timer::Sound, 255);
         assert_eq!(255, ram.get_timer_register(Timer::Sound));
     }
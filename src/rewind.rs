@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+use crate::chip8::Chip8;
+use crate::save_state;
+use crate::scheduler::Scheduler;
+use crate::Result;
+
+/// Ring buffer of per frame snapshots for rewinding gameplay.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as
+/// the run length encoded XOR of itself and the snapshot after it - from
+/// one frame to the next almost nothing changes, so each delta is a few
+/// bytes. XOR is its own inverse, so stepping back a frame is applying
+/// the newest delta to the newest snapshot.
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+    capacity: usize,
+}
+
+struct Delta {
+    /// length of the older snapshot
+    len: usize,
+    /// run length encoded XOR of the older and newer snapshots
+    encoded: Vec<u8>,
+}
+
+impl Rewind {
+    pub const DEFAULT_SECONDS: usize = 30;
+
+    /// keeps at most capacity frames of history
+    pub fn new(capacity: usize) -> Self {
+        Rewind {
+            latest: None,
+            deltas: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// records the machine as the newest frame, dropping the oldest frame when full
+    pub fn push(&mut self, chip8: &Chip8) -> Result<()> {
+        let snapshot = save_state::to_bytes(chip8)?;
+
+        if let Some(previous) = self.latest.take() {
+            if self.deltas.len() == self.capacity {
+                self.deltas.pop_front();
+            }
+
+            self.deltas.push_back(Delta {
+                len: previous.len(),
+                encoded: encode(&xor(&previous, &snapshot)),
+            });
+        }
+
+        self.latest = Some(snapshot);
+        Ok(())
+    }
+
+    /// steps back a frame. Returns None once the history runs out.
+    pub fn pop(&mut self) -> Option<Result<Chip8>> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.take()?;
+
+        let mut previous = xor(&latest, &decode(&delta.encoded));
+        previous.resize(delta.len, 0);
+
+        let restored = save_state::from_bytes(&previous);
+        self.latest = Some(previous);
+
+        Some(restored)
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SECONDS * Scheduler::TIMER_HZ as usize)
+    }
+}
+
+/// XORs two byte strings, treating the shorter one as zero padded
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|idx| a.get(idx).unwrap_or(&0) ^ b.get(idx).unwrap_or(&0))
+        .collect()
+}
+
+/// Encodes bytes as repeated [zero run length, literal length, literals..]
+/// with both lengths as LEB128 varints. Trailing zeros are dropped.
+fn encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![];
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let zeros = bytes[idx..].iter().take_while(|&&byte| byte == 0).count();
+        idx += zeros;
+
+        if idx == bytes.len() {
+            break;
+        }
+
+        let literals = bytes[idx..].iter().take_while(|&&byte| byte != 0).count();
+
+        write_varint(&mut encoded, zeros);
+        write_varint(&mut encoded, literals);
+        encoded.extend_from_slice(&bytes[idx..idx + literals]);
+        idx += literals;
+    }
+
+    encoded
+}
+
+fn decode(encoded: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut idx = 0;
+
+    while idx < encoded.len() {
+        let zeros = read_varint(encoded, &mut idx);
+        let literals = read_varint(encoded, &mut idx);
+
+        bytes.resize(bytes.len() + zeros, 0);
+        bytes.extend_from_slice(&encoded[idx..idx + literals]);
+        idx += literals;
+    }
+
+    bytes
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], idx: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    while let Some(&byte) = bytes.get(*idx) {
+        *idx += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // 7001 1200 - ADD V0, 1; JP 0x200
+    const PROGRAM: &[u8] = &[0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn round_trips_encoding() {
+        let bytes = [0, 0, 0, 5, 6, 0, 0, 7, 0, 0];
+        let decoded = decode(&encode(&bytes));
+        assert_eq!(decoded, [0, 0, 0, 5, 6, 0, 0, 7]);
+
+        let long_run = [vec![0; 300], vec![1]].concat();
+        assert_eq!(decode(&encode(&long_run)), long_run);
+    }
+
+    #[test]
+    fn rewinds_frames_in_order() {
+        let mut rewind = Rewind::new(10);
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+
+        for _ in 0..5 {
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+            rewind.push(&chip8).unwrap();
+        }
+        assert_eq!(chip8.ram.V[0], 5);
+
+        for expected in (1..5).rev() {
+            let restored = rewind.pop().unwrap().unwrap();
+            assert_eq!(restored.ram.V[0], expected);
+        }
+
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn drops_oldest_frames() {
+        let mut rewind = Rewind::new(2);
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+
+        for _ in 0..5 {
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+            rewind.push(&chip8).unwrap();
+        }
+
+        assert_eq!(rewind.pop().unwrap().unwrap().ram.V[0], 4);
+        assert_eq!(rewind.pop().unwrap().unwrap().ram.V[0], 3);
+        assert!(rewind.pop().is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::emulator::Settings;
+use crate::quirks::Quirks;
+use crate::{Error, Result};
+
+/// What's known about a ROM - the platform it was written for and how to
+/// play it, so games run right without picking quirks by hand.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomInfo {
+    pub title: String,
+    pub author: Option<String>,
+    /// a quirk preset - see Quirks::PRESETS
+    pub platform: String,
+    /// overrides on top of the platform's quirks, Ex. "wrap=true"
+    #[serde(default)]
+    pub quirks: String,
+    /// instructions per 60Hz frame
+    pub tick_rate: Option<u32>,
+    /// what the keypad keys do, by hex key
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+}
+
+impl RomInfo {
+    pub fn quirks(&self) -> Result<Quirks> {
+        let mut quirks = self.platform.parse::<Quirks>()?;
+        quirks.apply_overrides(&self.quirks)?;
+        Ok(quirks)
+    }
+
+    /// sets settings up for this ROM
+    pub fn apply(&self, settings: &mut Settings) -> Result<()> {
+        settings.quirks = self.quirks()?;
+        if let Some(tick_rate) = self.tick_rate {
+            settings.ips = tick_rate * 60;
+        }
+        Ok(())
+    }
+
+    /// Ex. "2 up, 4 left"
+    pub fn key_hints(&self) -> String {
+        self.keys
+            .iter()
+            .map(|(key, action)| format!("{} {}", key.to_uppercase(), action))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// ROM metadata keyed by SHA-1 - see assets/roms.toml
+#[derive(Debug, Default)]
+pub struct RomDb {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDb {
+    /// the database compiled into the binary
+    pub fn bundled() -> &'static RomDb {
+        static BUNDLED: OnceLock<RomDb> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            include_str!("../assets/roms.toml")
+                .parse()
+                .expect("assets/roms.toml is invalid")
+        })
+    }
+
+    /// looks a ROM up by its SHA-1 - see cli::game::hash
+    pub fn get(&self, hash: &str) -> Option<&RomInfo> {
+        self.roms.get(hash)
+    }
+}
+
+impl FromStr for RomDb {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Ok(RomDb {
+            roms: toml::from_str(text)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::game;
+
+    #[test]
+    fn bundled_entries_are_valid() {
+        for (hash, info) in &RomDb::bundled().roms {
+            assert_eq!(hash.len(), 40, "{}", hash);
+            assert!(info.quirks().is_ok(), "{}", info.title);
+            for key in info.keys.keys() {
+                assert!(u8::from_str_radix(key, 16).is_ok_and(|key| key <= 0xF));
+            }
+        }
+
+        let astro = RomDb::bundled()
+            .get(&game::hash(include_bytes!("../games/Astro.ch8")))
+            .unwrap();
+        assert_eq!(astro.title, "Astro Dodge");
+        assert!(astro.key_hints().starts_with("2 up, 4 left"));
+    }
+
+    #[test]
+    fn configures_settings() {
+        let db = r#"
+[abc]
+title = "Spacefight 2091!"
+platform = "schip"
+quirks = "wrap=true"
+tick_rate = 30
+"#
+        .parse::<RomDb>()
+        .unwrap();
+
+        let mut settings = Settings::default();
+        db.get("abc").unwrap().apply(&mut settings).unwrap();
+        assert_eq!(settings.ips, 1800);
+        assert!(settings.quirks.wrap_sprites);
+        assert!(!settings.quirks.load_store_increments_i);
+
+        assert!("[abc]\nplatform = \"vip\"".parse::<RomDb>().is_err());
+        assert!(db.get("def").is_none());
+    }
+}
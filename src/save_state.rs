@@ -0,0 +1,290 @@
+use std::{error, fmt, fs, path::PathBuf};
+
+use crate::chip8::Chip8;
+use crate::cli::game;
+use crate::display::Display;
+use crate::Result;
+
+/// "C8SS" followed by a format version, then the bincode encoded Chip8
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u8 = 4;
+
+/// Why a save state can't be restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveStateError {
+    /// the bytes don't start with MAGIC
+    NotASaveState,
+    /// written by another version of the format
+    Version { found: u8, expected: u8 },
+    /// the framebuffer is neither the lores nor the hires size it claims to be
+    Canvas { width: usize, height: usize },
+    /// a pixel lit on planes that don't exist
+    Pixel { value: u8 },
+    /// drawing to planes that don't exist
+    Planes { planes: u8 },
+    /// FX0A waiting to fill a register past VF
+    Register { register: u8 },
+    /// FX0A waiting on the release of a key past 0xF
+    Key { hex: u8 },
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::NotASaveState => write!(f, "not a chippy save state"),
+            SaveStateError::Version { found, expected } => write!(
+                f,
+                "save state version {} is not supported - expected {}",
+                found, expected
+            ),
+            SaveStateError::Canvas { width, height } => {
+                write!(f, "save state has a broken {}x{} display", width, height)
+            }
+            SaveStateError::Pixel { value } => {
+                write!(f, "save state has a pixel set to {:#04X}", value)
+            }
+            SaveStateError::Planes { planes } => {
+                write!(f, "save state draws to planes {:#04X}", planes)
+            }
+            SaveStateError::Register { register } => {
+                write!(
+                    f,
+                    "save state waits on a key for register {:#04X}",
+                    register
+                )
+            }
+            SaveStateError::Key { hex } => {
+                write!(f, "save state waits on the release of key {:#04X}", hex)
+            }
+        }
+    }
+}
+
+impl error::Error for SaveStateError {}
+
+/// Serializes the whole machine - memory, registers, stack, timers,
+/// framebuffer, keypad, RNG and clock.
+pub fn to_bytes(chip8: &Chip8) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bincode::serialize_into(&mut bytes, chip8)?;
+
+    Ok(bytes)
+}
+
+/// Restores a machine serialized with to_bytes
+pub fn from_bytes(bytes: &[u8]) -> Result<Chip8> {
+    let header_len = MAGIC.len() + 1;
+
+    if bytes.len() < header_len || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(SaveStateError::NotASaveState.into());
+    }
+
+    if bytes[MAGIC.len()] != VERSION {
+        return Err(SaveStateError::Version {
+            found: bytes[MAGIC.len()],
+            expected: VERSION,
+        }
+        .into());
+    }
+
+    let mut chip8: Chip8 = bincode::deserialize(&bytes[header_len..])?;
+    check_display(&chip8.display)?;
+    check_key_wait(&chip8)?;
+    chip8.force_redraw();
+
+    Ok(chip8)
+}
+
+/// everything indexing the canvas assumes it's a full lores or hires grid
+fn check_display(display: &Display) -> std::result::Result<(), SaveStateError> {
+    let canvas = &display.virtual_canvas;
+    let height = canvas.len();
+    let width = canvas.first().map_or(0, Vec::len);
+    let expected = if display.hires {
+        Display::HIRES
+    } else {
+        Display::LORES
+    };
+
+    if (width, height) != expected || canvas.iter().any(|row| row.len() != width) {
+        return Err(SaveStateError::Canvas { width, height });
+    }
+
+    // two bitplanes, so a pixel indexes a four color palette
+    if let Some(&value) = canvas.iter().flatten().find(|&&pixel| pixel > 0b11) {
+        return Err(SaveStateError::Pixel { value });
+    }
+    if display.planes > 0b11 {
+        return Err(SaveStateError::Planes {
+            planes: display.planes,
+        });
+    }
+    Ok(())
+}
+
+/// FX0A's register and key index V and the keypad once the key's released
+fn check_key_wait(chip8: &Chip8) -> std::result::Result<(), SaveStateError> {
+    match chip8.key_wait() {
+        Some((register, _)) if register > 0xF => Err(SaveStateError::Register { register }),
+        Some((_, Some(hex))) if hex > 0xF => Err(SaveStateError::Key { hex }),
+        _ => Ok(()),
+    }
+}
+
+/// Numbered save slots for a single ROM. Slots live in their own
+/// directory, keyed by the ROM's SHA-1 so renamed files keep their saves.
+pub struct SaveSlots {
+    dir: PathBuf,
+}
+
+impl SaveSlots {
+    pub const SLOT_COUNT: u8 = 10;
+
+    pub fn new(dir: PathBuf) -> Self {
+        SaveSlots { dir }
+    }
+
+    /// slots under the user's data directory for program
+    pub fn for_program(program: &[u8]) -> Self {
+        let hash = game::hash(program);
+        let data_dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+
+        Self::new(data_dir.join("chippy").join("saves").join(hash))
+    }
+
+    fn slot_path(&self, slot: u8) -> Result<PathBuf> {
+        if slot >= Self::SLOT_COUNT {
+            return Err(format!("save slot must be 0-{}", Self::SLOT_COUNT - 1).into());
+        }
+
+        Ok(self.dir.join(format!("slot{}.state", slot)))
+    }
+
+    /// writes chip8 to slot, overwriting whatever was there
+    pub fn save(&self, slot: u8, chip8: &Chip8) -> Result<PathBuf> {
+        let path = self.slot_path(slot)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, to_bytes(chip8)?)?;
+
+        Ok(path)
+    }
+
+    /// reads the machine saved in slot
+    pub fn load(&self, slot: u8) -> Result<Chip8> {
+        let path = self.slot_path(slot)?;
+        let bytes = fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // 6A2F A300 FA33 C0FF - LD VA, 0x2F; LD I, 0x300; LD B, VA; RND V0, 0xFF
+    const PROGRAM: &[u8] = &[0x6A, 0x2F, 0xA3, 0x00, 0xFA, 0x33, 0xC0, 0xFF, 0xC1, 0xFF];
+
+    #[test]
+    fn round_trips_machine_state() {
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::schip()).unwrap();
+        (0..4).for_each(|_| chip8.step().unwrap());
+        chip8.set_key(0x3, true);
+        chip8.display.virtual_canvas[3][4] = 1;
+
+        let mut restored = from_bytes(&to_bytes(&chip8).unwrap()).unwrap();
+
+        assert_eq!(restored.ram.PC, chip8.ram.PC);
+        assert_eq!(restored.ram.V, chip8.ram.V);
+        assert_eq!(restored.ram.I, chip8.ram.I);
+        assert_eq!(restored.ram.mem, chip8.ram.mem);
+        assert_eq!(restored.quirks, chip8.quirks);
+        assert!(restored.is_pressed(0x3));
+        assert!(restored.display.virtual_canvas[3][4] == 1);
+        assert!(restored.take_draw_flag());
+
+        // the rng picks up where the saved machine left off
+        chip8.step().unwrap();
+        restored.step().unwrap();
+        assert_eq!(restored.ram.V[1], chip8.ram.V[1]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(from_bytes(b"not a save").is_err());
+        assert!(from_bytes(b"C8SS\x7F").is_err());
+    }
+
+    #[test]
+    fn rejects_broken_displays() {
+        let broken = |change: fn(&mut Display)| {
+            let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+            change(&mut chip8.display);
+            let err = from_bytes(&to_bytes(&chip8).unwrap()).err().unwrap();
+            err.downcast_ref::<SaveStateError>().cloned()
+        };
+
+        assert_eq!(
+            broken(|display| display.virtual_canvas.clear()),
+            Some(SaveStateError::Canvas {
+                width: 0,
+                height: 0
+            })
+        );
+        assert!(broken(|display| display.virtual_canvas[5].truncate(10)).is_some());
+        assert!(broken(|display| display.hires = true).is_some());
+        assert_eq!(
+            broken(|display| display.virtual_canvas[2][7] = 4),
+            Some(SaveStateError::Pixel { value: 4 })
+        );
+        assert_eq!(
+            broken(|display| display.planes = 0xFF),
+            Some(SaveStateError::Planes { planes: 0xFF })
+        );
+    }
+
+    #[test]
+    fn rejects_broken_key_waits() {
+        // F30A - LD V3, K
+        let mut chip8 = Chip8::new(&[0xF3, 0x0A], Quirks::default()).unwrap();
+        chip8.step().unwrap();
+        chip8.set_key(0x5, true);
+        let bytes = to_bytes(&chip8).unwrap();
+
+        // the machine ends in the key wait's register and pressed key, then
+        // the executed count and the halted flag
+        let register = bytes.len() - 12;
+        let pressed = bytes.len() - 10;
+        assert_eq!((bytes[register], bytes[pressed]), (0x3, 0x5));
+        assert!(from_bytes(&bytes).is_ok());
+
+        let corrupt = |at: usize| {
+            let mut bytes = bytes.clone();
+            bytes[at] = 0x10;
+            let err = from_bytes(&bytes).err().unwrap();
+            err.downcast_ref::<SaveStateError>().cloned()
+        };
+        assert_eq!(
+            corrupt(register),
+            Some(SaveStateError::Register { register: 0x10 })
+        );
+        assert_eq!(corrupt(pressed), Some(SaveStateError::Key { hex: 0x10 }));
+    }
+
+    #[test]
+    fn saves_and_loads_slots() {
+        let dir = std::env::temp_dir().join(format!("chippy-slots-{}", std::process::id()));
+        let slots = SaveSlots::new(dir.clone());
+        let mut chip8 = Chip8::new(PROGRAM, Quirks::default()).unwrap();
+        chip8.step().unwrap();
+
+        slots.save(3, &chip8).unwrap();
+        assert_eq!(slots.load(3).unwrap().ram.V[0xA], 0x2F);
+        assert!(slots.load(4).is_err());
+        assert!(slots.save(10, &chip8).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
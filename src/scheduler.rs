@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Splits emulated time into 60 Hz frames.
+///
+/// The CPU runs `ips` instructions per emulated second, spread as evenly
+/// as possible over the 60 frames that make up that second. The delay and
+/// sound timers tick once at the end of every frame, so they run at 60 Hz
+/// whatever the CPU speed is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scheduler {
+    ips: u32,
+    /// frames completed since boot
+    frame: u64,
+    /// instructions executed in the current frame
+    frame_cycles: u32,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_IPS)
+    }
+}
+
+impl Scheduler {
+    pub const TIMER_HZ: u32 = 60;
+    pub const DEFAULT_IPS: u32 = 700;
+    /// real time a single frame takes
+    pub const FRAME_DURATION: Duration =
+        Duration::from_nanos(1_000_000_000 / Self::TIMER_HZ as u64);
+
+    /// ips is clamped so that every frame runs at least one instruction
+    pub fn new(ips: u32) -> Self {
+        Scheduler {
+            ips: ips.max(Self::TIMER_HZ),
+            frame: 0,
+            frame_cycles: 0,
+        }
+    }
+
+    /// changes CPU speed without touching the timers
+    pub fn set_ips(&mut self, ips: u32) {
+        self.ips = ips.max(Self::TIMER_HZ);
+        self.frame_cycles = self.frame_cycles.min(self.cycles_in_frame(self.frame) - 1);
+    }
+
+    /// frames completed since boot
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// number of instructions the given frame runs
+    pub fn cycles_in_frame(&self, frame: u64) -> u32 {
+        let second_frame = frame % Self::TIMER_HZ as u64;
+        let cycles_before = |frame: u64| frame * self.ips as u64 / Self::TIMER_HZ as u64;
+
+        (cycles_before(second_frame + 1) - cycles_before(second_frame)) as u32
+    }
+
+    /// records an executed instruction. Returns true if it completed the frame.
+    pub fn tick(&mut self) -> bool {
+        self.frame_cycles += 1;
+
+        if self.frame_cycles >= self.cycles_in_frame(self.frame) {
+            self.frame += 1;
+            self.frame_cycles = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_ips_over_a_second() {
+        let scheduler = Scheduler::new(700);
+        let total: u32 = (0..60).map(|frame| scheduler.cycles_in_frame(frame)).sum();
+        assert_eq!(total, 700);
+        assert!((0..60).all(|frame| (11..=12).contains(&scheduler.cycles_in_frame(frame))));
+    }
+
+    #[test]
+    fn completes_frames() {
+        let mut scheduler = Scheduler::new(120);
+        assert!(!scheduler.tick());
+        assert!(scheduler.tick());
+        assert_eq!(scheduler.frame(), 1);
+    }
+
+    #[test]
+    fn clamps_ips() {
+        let scheduler = Scheduler::new(1);
+        assert_eq!(scheduler, Scheduler::new(Scheduler::TIMER_HZ));
+        assert_eq!(scheduler.cycles_in_frame(0), 1);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::debugger::command::parse_number;
+use crate::{Error, Result};
+
+/// Names for addresses, from a symbol file or the assembler's labels.
+///
+/// Symbol files have one `name = 0x2A6` a line, the same syntax the
+/// assembler uses for constants. `0x2A6 name` and `name 0x2A6` are also
+/// accepted, and `;` or `#` starts a comment.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<usize, String>,
+}
+
+impl Symbols {
+    pub fn insert(&mut self, addr: usize, name: &str) {
+        self.names.insert(addr, name.to_string());
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// the name of exactly addr
+    pub fn get(&self, addr: usize) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    /// addr relative to the nearest symbol at or before it, Ex. `main_loop+0x4`
+    pub fn name(&self, addr: usize) -> Option<String> {
+        let (&start, name) = self.names.range(..=addr).next_back()?;
+        Some(match addr - start {
+            0 => name.clone(),
+            offset => format!("{}+{:#X}", name, offset),
+        })
+    }
+
+    /// name, falling back on the address in hex
+    pub fn format(&self, addr: usize) -> String {
+        self.name(addr).unwrap_or_else(|| format!("{:#05X}", addr))
+    }
+}
+
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, name) in &self.names {
+            writeln!(f, "{} = {:#05X}", name, addr)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut symbols = Symbols::default();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split([';', '#']).next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields = line
+                .split(|c: char| c == '=' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .collect::<Vec<&str>>();
+
+            let symbol = match fields[..] {
+                [name, addr] if parse_number(addr).is_ok() => Some((name, addr)),
+                [addr, name] if parse_number(addr).is_ok() => Some((name, addr)),
+                _ => None,
+            };
+            let (name, addr) =
+                symbol.ok_or_else(|| format!("line {}: expected name = address", idx + 1))?;
+
+            symbols.insert(parse_number(addr)?, name);
+        }
+
+        Ok(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_symbol_files() {
+        let symbols = "; from the assembler\nmain_loop = 0x2A2\n0x300 draw_ship\nscore 0x400\n"
+            .parse::<Symbols>()
+            .unwrap();
+
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.get(0x300), Some("draw_ship"));
+        assert_eq!(symbols.get(0x400), Some("score"));
+        assert!("main_loop".parse::<Symbols>().is_err());
+
+        // round trips through Display
+        assert_eq!(symbols.to_string().parse::<Symbols>().unwrap(), symbols);
+    }
+
+    #[test]
+    fn names_addresses_relative_to_symbols() {
+        let symbols = "main_loop = 0x2A2".parse::<Symbols>().unwrap();
+
+        assert_eq!(symbols.format(0x2A2), "main_loop");
+        assert_eq!(symbols.format(0x2A6), "main_loop+0x4");
+        assert_eq!(symbols.format(0x200), "0x200");
+    }
+}
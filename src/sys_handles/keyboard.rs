@@ -1,11 +1,12 @@
 use sdl2::keyboard::Scancode;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 
+use crate::Result;
+
+/// Maps host scancodes onto the 16 key hex keypad. Which keys are held
+/// lives on the Chip8 itself.
 pub struct Keyboard {
-    pub hex_to_scancode: HashMap<u8, Scancode>,
     pub scancode_to_hex: HashMap<Scancode, u8>,
-    pressed_keys: HashSet<u8>,
-    last_pressed: Option<u8>,
 }
 
 const HEX_TO_SCANCODE: [(u8, Scancode); 16] = [
@@ -28,38 +29,27 @@ const HEX_TO_SCANCODE: [(u8, Scancode); 16] = [
 ];
 
 impl Keyboard {
-    pub fn new() -> Self {
-        let scancode_to_hex: [(Scancode, u8); 16] = HEX_TO_SCANCODE
+    /// the default layout with keymap's hex keys moved onto the named
+    /// SDL keys, Ex. {0xA: "Space"}. A remapped key takes over the host
+    /// key from whichever hex key it defaulted to.
+    pub fn new(keymap: &BTreeMap<u8, String>) -> Result<Self> {
+        let mut scancode_to_hex = HEX_TO_SCANCODE
             .iter()
+            .filter(|(hex, _)| !keymap.contains_key(hex))
             .map(|(hex, scan)| (*scan, *hex))
-            .collect::<Vec<(Scancode, u8)>>()
-            .try_into()
-            .unwrap();
+            .collect::<HashMap<Scancode, u8>>();
 
-        Keyboard {
-            hex_to_scancode: HashMap::from(HEX_TO_SCANCODE),
-            scancode_to_hex: HashMap::from(scancode_to_hex),
-            pressed_keys: HashSet::new(),
-            last_pressed: None,
+        for (&hex, name) in keymap {
+            let scancode = Scancode::from_name(name)
+                .ok_or_else(|| format!("unknown key '{}' for keypad {:X}", name, hex))?;
+            scancode_to_hex.insert(scancode, hex);
         }
-    }
-
-    pub fn press_key(&mut self, scancode: Scancode) {
-        self.release_key(self.last_pressed);
 
-        if let Some(&code) = self.scancode_to_hex.get(&scancode) {
-            self.pressed_keys.insert(code);
-            self.last_pressed = Some(code);
-        };
-    }
-
-    pub fn release_key(&mut self, hex_code: Option<u8>) {
-        if let Some(hex) = hex_code {
-            self.pressed_keys.remove(&hex);
-        }
+        Ok(Keyboard { scancode_to_hex })
     }
 
-    pub fn is_pressed(&self, keycode: u8) -> bool {
-        self.pressed_keys.contains(&keycode)
+    /// Returns the hex key mapped to scancode
+    pub fn to_hex(&self, scancode: Scancode) -> Option<u8> {
+        self.scancode_to_hex.get(&scancode).copied()
     }
 }
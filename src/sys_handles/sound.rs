@@ -3,6 +3,24 @@ use sdl2::{
     Sdl,
 };
 
+/// The tone played while the sound timer is running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beep {
+    /// pitch in Hz
+    pub frequency: f32,
+    /// 0.0 - 1.0
+    pub volume: f32,
+}
+
+impl Default for Beep {
+    fn default() -> Self {
+        Beep {
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SquareWave {
     phase_inc: f32,
@@ -31,7 +49,7 @@ pub struct SoundSystem {
 }
 
 impl SoundSystem {
-    pub fn new(sdl_ctx: &Sdl) -> Self {
+    pub fn new(sdl_ctx: &Sdl, beep: Beep) -> Self {
         let audio_subsystem = sdl_ctx.audio().unwrap();
 
         let desired_spec = AudioSpecDesired {
@@ -44,9 +62,9 @@ impl SoundSystem {
             .open_playback(None, &desired_spec, |spec| {
                 // initialize the audio callback
                 SquareWave {
-                    phase_inc: 440.0 / spec.freq as f32,
+                    phase_inc: beep.frequency / spec.freq as f32,
                     phase: 0.0,
-                    volume: 0.25,
+                    volume: beep.volume,
                 }
             })
             .unwrap();
@@ -1,21 +1,32 @@
-use sdl2::{pixels, render::WindowCanvas, Sdl};
+use sdl2::{rect::Rect, render::WindowCanvas, Sdl};
 
 use crate::Result;
 
+/// a width x height image of [r, g, b, 0] pixels
+pub struct Frame<'a> {
+    pub bytes: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+}
+
 pub trait Renderable {
-    fn render(&mut self, bytes: &[u8]) -> Result<()>;
-    fn clear(&mut self) -> Result<()>;
+    /// renders the game, with panel (if any) scaled to the same height on its right
+    fn render(&mut self, game: Frame, panel: Option<Frame>) -> Result<()>;
 }
 
 struct Canvas<T> {
     canvas: T,
+    /// window area the game is scaled into
+    game_size: (u32, u32),
 }
 
-impl From<&Sdl> for Canvas<WindowCanvas> {
-    fn from(sdl_ctx: &Sdl) -> Self {
+impl Canvas<WindowCanvas> {
+    /// scale is window pixels per 64x32 display pixel
+    fn new(sdl_ctx: &Sdl, scale: u32) -> Self {
+        let game_size = (64 * scale, 32 * scale);
         let video_subsystem = sdl_ctx.video().unwrap();
         let window = video_subsystem
-            .window("Chip8", 64 * 10, 32 * 10)
+            .window("Chip8", game_size.0, game_size.1)
             .position_centered()
             .build()
             .unwrap();
@@ -27,31 +38,45 @@ impl From<&Sdl> for Canvas<WindowCanvas> {
             .build()
             .unwrap();
 
-        Canvas { canvas }
+        Canvas { canvas, game_size }
     }
 }
 
 impl Renderable for Canvas<WindowCanvas> {
-    /// creates a texture from byte array and renders onto canvas
-    fn render(&mut self, bytes: &[u8]) -> Result<()> {
+    /// creates textures from the frames and renders them onto canvas, widening
+    /// the window to fit the panel
+    fn render(&mut self, game: Frame, panel: Option<Frame>) -> Result<()> {
+        let (game_width, height) = self.game_size;
+        let panel_width = panel
+            .as_ref()
+            .map_or(0, |panel| panel.width * height / panel.height);
+
+        let size = (game_width + panel_width, height);
+        if self.canvas.window().size() != size {
+            self.canvas.window_mut().set_size(size.0, size.1)?;
+        }
+
         let creator = self.canvas.texture_creator();
-        // create a texture that matches our virtual display dimensions
-        let mut texture =
-            creator.create_texture_streaming(creator.default_pixel_format(), 64, 32)?;
-        // pitch - bytes per row - 4 bytes per pixel [r, g, b, 0] (final 0 is padding)
-        texture.update(None, bytes, 64 * 4)?;
+        let areas = [
+            Some((game, Rect::new(0, 0, game_width, height))),
+            panel.map(|panel| (panel, Rect::new(game_width as i32, 0, panel_width, height))),
+        ];
 
-        self.canvas.copy(&texture, None, None)?;
+        for (frame, area) in areas.into_iter().flatten() {
+            // create a texture that matches the frame dimensions
+            let mut texture = creator.create_texture_streaming(
+                creator.default_pixel_format(),
+                frame.width,
+                frame.height,
+            )?;
+            // pitch - bytes per row - 4 bytes per pixel [r, g, b, 0] (final 0 is padding)
+            texture.update(None, frame.bytes, frame.width as usize * 4)?;
+            self.canvas.copy(&texture, None, area)?;
+        }
         self.canvas.present();
 
         Ok(())
     }
-
-    fn clear(&mut self) -> Result<()> {
-        self.canvas.set_draw_color(pixels::Color::RGB(255, 0, 20));
-        self.canvas.clear();
-        Ok(())
-    }
 }
 
 pub struct Renderer {
@@ -59,19 +84,16 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn clear(&mut self) -> Result<()> {
-        self.canvas.clear()
-    }
-
-    pub fn render(&mut self, bytes: &[u8]) -> Result<()> {
-        self.canvas.render(bytes)
+    pub fn render(&mut self, game: Frame, panel: Option<Frame>) -> Result<()> {
+        self.canvas.render(game, panel)
     }
 }
 
-impl From<&Sdl> for Renderer {
-    fn from(sdl_ctx: &Sdl) -> Self {
+impl Renderer {
+    /// opens a window scale times the size of the 64x32 display
+    pub fn new(sdl_ctx: &Sdl, scale: u32) -> Self {
         Renderer {
-            canvas: Box::new(Canvas::from(sdl_ctx)),
+            canvas: Box::new(Canvas::new(sdl_ctx, scale)),
         }
     }
 }